@@ -0,0 +1,98 @@
+use core::time::Duration;
+
+/// Debounces a digital input that is read from time to time, like the
+/// contacts of a limit switch. A change of the level is only accepted once
+/// the input has kept the new level for the debounce time, so the bouncing
+/// of the contacts is ignored. Times are relative to any fixed moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Debouncer {
+    /// the accepted level
+    stable: bool,
+    /// the level of the last read
+    raw: bool,
+    /// the time of the read at which the input got its last level
+    since: Duration,
+    /// the time that a level has to last to be accepted
+    time: Duration,
+}
+
+impl Debouncer {
+    /// Returns a new debouncer that starts at the specified level.
+    pub fn new(level: bool, time: Duration) -> Debouncer {
+        return Debouncer {
+            stable: level,
+            raw: level,
+            since: Duration::ZERO,
+            time,
+        };
+    }
+
+    /// Sets the time that a level has to last to be accepted.
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+
+    /// Returns the time that a level has to last to be accepted.
+    pub fn time(&self) -> Duration {
+        return self.time;
+    }
+
+    /// Updates the debouncer with a level that was read at the specified
+    /// time, and returns the accepted level.
+    pub fn update(&mut self, level: bool, at: Duration) -> bool {
+        if level != self.raw {
+            self.raw = level;
+            self.since = at;
+        }
+
+        if self.raw != self.stable && at.saturating_sub(self.since) >= self.time {
+            self.stable = self.raw;
+        }
+
+        return self.stable;
+    }
+
+    /// Returns the accepted level.
+    pub fn level(&self) -> bool {
+        return self.stable;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        return Duration::from_millis(n);
+    }
+
+    #[test]
+    fn ignores_bounces_test() {
+        let mut debouncer = Debouncer::new(false, ms(5));
+
+        // the contacts bounce for 3 ms before they close
+        assert!(!debouncer.update(true, ms(10)));
+        assert!(!debouncer.update(false, ms(11)));
+        assert!(!debouncer.update(true, ms(13)));
+        assert!(!debouncer.update(true, ms(17)));
+        assert!(debouncer.update(true, ms(18)));
+        assert!(debouncer.level());
+
+        // a short glitch while closed is ignored
+        assert!(debouncer.update(false, ms(30)));
+        assert!(debouncer.update(true, ms(32)));
+        assert!(debouncer.update(true, ms(40)));
+    }
+
+    #[test]
+    fn without_time_test() {
+        let mut debouncer = Debouncer::new(true, Duration::ZERO);
+        assert!(!debouncer.update(false, ms(1)));
+        assert!(debouncer.update(true, ms(1)));
+
+        debouncer.set_time(ms(2));
+        assert_eq!(ms(2), debouncer.time());
+        assert!(debouncer.update(false, ms(2)));
+        assert!(!debouncer.update(false, ms(4)));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::motor::{Direction, StopMode};
+
+/// The state of the two L293D inputs of a motor slot. A running motor has
+/// one input high, a braking motor has both inputs high and a released
+/// motor has both inputs low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState {
+    Run(Direction),
+    Stopped(StopMode),
+}
+
+/// Returns the bits of the shift register byte that set the direction of the
+/// motor in the specified slot. The slots are numbered 1 to 4 like the M
+/// slots on the motor shield; any other slot has no bits.
+///
+/// | Motor | Clockwise | Counterclockwise |
+/// |-------|-----------|------------------|
+/// | M1    | 4         | 8                |
+/// | M2    | 2         | 16               |
+/// | M3    | 1         | 64               |
+/// | M4    | 32        | 128              |
+pub fn direction_bits(slot: usize, d: Direction) -> u8 {
+    return match (slot, d) {
+        (1, Direction::Clockwise) => 4,
+        (1, Direction::Counterclockwise) => 8,
+        (2, Direction::Clockwise) => 2,
+        (2, Direction::Counterclockwise) => 16,
+        (3, Direction::Clockwise) => 1,
+        (3, Direction::Counterclockwise) => 64,
+        (4, Direction::Clockwise) => 32,
+        (4, Direction::Counterclockwise) => 128,
+        _ => 0,
+    };
+}
+
+impl SlotState {
+    /// Returns the state for a motor that is wired the other way around. A
+    /// running motor gets the opposite direction, a stopped motor stays the
+    /// same since both of its inputs are equal.
+    pub fn inverted(self) -> SlotState {
+        return match self {
+            SlotState::Run(d) => SlotState::Run(d.inverted()),
+            SlotState::Stopped(m) => SlotState::Stopped(m),
+        };
+    }
+}
+
+/// Returns the bits of the shift register byte that set the state of the
+/// motor in the specified slot. Braking sets the bits of both directions.
+pub fn state_bits(slot: usize, s: SlotState) -> u8 {
+    return match s {
+        SlotState::Run(d) => direction_bits(slot, d),
+        SlotState::Stopped(StopMode::Brake) => {
+            direction_bits(slot, Direction::Clockwise) | direction_bits(slot, Direction::Counterclockwise)
+        },
+        SlotState::Stopped(StopMode::Release) => 0,
+    };
+}
+
+/// Calculates the byte that holds the states of all four motor slots. The
+/// bits of every slot are combined with a bitwise OR, since pushing only the
+/// bits of one motor would make all other motors stall. A slot without a
+/// motor contributes no bits.
+pub fn calculate_states(slots: [Option<SlotState>; 4]) -> u8 {
+    let mut states: u8 = 0;
+    for (i, slot) in slots.iter().enumerate() {
+        if let Some(s) = slot {
+            states |= state_bits(i + 1, *s);
+        }
+    }
+
+    return states;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_bits_test() {
+        assert_eq!(4, direction_bits(1, Direction::Clockwise));
+        assert_eq!(16, direction_bits(2, Direction::Counterclockwise));
+        assert_eq!(0, direction_bits(5, Direction::Clockwise));
+    }
+
+    #[test]
+    fn state_bits_test() {
+        assert_eq!(4, state_bits(1, SlotState::Run(Direction::Clockwise)));
+        assert_eq!(12, state_bits(1, SlotState::Stopped(StopMode::Brake)));
+        assert_eq!(65, state_bits(3, SlotState::Stopped(StopMode::Brake)));
+        assert_eq!(0, state_bits(3, SlotState::Stopped(StopMode::Release)));
+    }
+
+    #[test]
+    fn inverted_state_test() {
+        let run = SlotState::Run(Direction::Clockwise);
+        assert_eq!(SlotState::Run(Direction::Counterclockwise), run.inverted());
+        let brake = SlotState::Stopped(StopMode::Brake);
+        assert_eq!(brake, brake.inverted());
+    }
+
+    #[test]
+    fn calculate_states_test() {
+        let slots = [
+            Some(SlotState::Run(Direction::Clockwise)),
+            Some(SlotState::Stopped(StopMode::Brake)),
+            Some(SlotState::Stopped(StopMode::Release)),
+            None,
+        ];
+        assert_eq!(0b00010110, calculate_states(slots));
+        assert_eq!(0, calculate_states([None; 4]));
+    }
+}
@@ -0,0 +1,122 @@
+/// Returns the input with a deadband around zero. An input within the
+/// deadband becomes zero, and the rest of the range is scaled so the output
+/// still goes from zero to one just outside of the deadband.
+pub fn apply_deadband(value: f32, deadband: f32) -> f32 {
+    if value.abs() <= deadband {
+        return 0.0;
+    }
+
+    if deadband >= 1.0 {
+        return 0.0;
+    }
+
+    return match value > 0.0 {
+        true => (value - deadband) / (1.0 - deadband),
+        false => (value + deadband) / (1.0 - deadband),
+    };
+}
+
+/// Returns the value clamped to the range of a speed, from -1.0 to 1.0. A
+/// value that is not a number becomes zero.
+pub fn clamp_speed(value: f32) -> f32 {
+    if value.is_nan() {
+        return 0.0;
+    }
+
+    return value.clamp(-1.0, 1.0);
+}
+
+/// Scales the speeds down by the same factor so that none of them is
+/// faster than full speed. This keeps the ratio between the wheels, where
+/// clamping every wheel on its own would change the direction of motion.
+pub fn desaturate(speeds: &mut [f32]) {
+    let mut greatest: f32 = 1.0;
+    for s in speeds.iter() {
+        greatest = greatest.max(s.abs());
+    }
+
+    for s in speeds.iter_mut() {
+        *s /= greatest;
+    }
+}
+
+/// Returns the speeds of the left and right wheels for arcade driving, where
+/// the throttle drives forward and the turn rotates clockwise, seen from
+/// above.
+pub fn arcade(throttle: f32, turn: f32) -> (f32, f32) {
+    let throttle = clamp_speed(throttle);
+    let turn = clamp_speed(turn);
+    let mut speeds = [throttle + turn, throttle - turn];
+    desaturate(&mut speeds);
+    return (speeds[0], speeds[1]);
+}
+
+/// Returns the speeds of the left and right wheels for curvature driving,
+/// where the curvature sets how sharp the robot turns instead of how fast. A
+/// robot that does not move does not turn.
+pub fn curvature(throttle: f32, curvature: f32) -> (f32, f32) {
+    let throttle = clamp_speed(throttle);
+    let curvature = clamp_speed(curvature);
+    let turn = throttle.abs() * curvature;
+    let mut speeds = [throttle + turn, throttle - turn];
+    desaturate(&mut speeds);
+    return (speeds[0], speeds[1]);
+}
+
+/// Returns the speeds of the front left, front right, rear left and rear
+/// right wheels of a mecanum chassis, where vx drives forward, vy drives to
+/// the right and omega rotates clockwise, seen from above. The rollers of the
+/// wheels should form an X when the chassis is seen from above.
+pub fn mecanum(vx: f32, vy: f32, omega: f32) -> [f32; 4] {
+    let vx = clamp_speed(vx);
+    let vy = clamp_speed(vy);
+    let omega = clamp_speed(omega);
+    let mut speeds = [
+        vx + vy + omega,
+        vx - vy - omega,
+        vx - vy + omega,
+        vx + vy - omega,
+    ];
+    desaturate(&mut speeds);
+    return speeds;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_deadband_test() {
+        assert_eq!(0.0, apply_deadband(0.05, 0.1));
+        assert_eq!(0.0, apply_deadband(-0.1, 0.1));
+        assert_eq!(1.0, apply_deadband(1.0, 0.1));
+        assert_eq!(-0.5, apply_deadband(-0.75, 0.5));
+    }
+
+    #[test]
+    fn arcade_test() {
+        assert_eq!((0.5, 0.5), arcade(0.5, 0.0));
+        assert_eq!((1.0, 0.0), arcade(0.5, 0.5));
+        assert_eq!((1.0, 0.0), arcade(1.0, 1.0));
+        assert_eq!((1.0, -1.0), arcade(0.0, 2.0));
+    }
+
+    #[test]
+    fn curvature_test() {
+        assert_eq!((0.0, 0.0), curvature(0.0, 1.0));
+        assert_eq!((0.75, 0.25), curvature(0.5, 0.5));
+        assert_eq!((1.0, 0.0), curvature(1.0, 1.0));
+    }
+
+    #[test]
+    fn mecanum_test() {
+        assert_eq!([0.5, 0.5, 0.5, 0.5], mecanum(0.5, 0.0, 0.0));
+        assert_eq!([0.5, -0.5, -0.5, 0.5], mecanum(0.0, 0.5, 0.0));
+        assert_eq!([0.5, -0.5, 0.5, -0.5], mecanum(0.0, 0.0, 0.5));
+
+        // the ratio between the wheels is kept when a wheel saturates
+        assert_eq!([1.0, 0.0, 0.0, 1.0], mecanum(1.0, 1.0, 0.0));
+        assert_eq!([1.0, 0.0, 1.0, 0.0], mecanum(1.0, 0.0, 1.0));
+        assert_eq!([1.0, 0.0, 0.5, 0.5], mecanum(0.5, 0.25, 0.25));
+    }
+}
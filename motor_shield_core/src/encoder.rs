@@ -0,0 +1,187 @@
+use core::time::Duration;
+
+/// The number of counts over which the velocity of an encoder is estimated.
+pub const WINDOW: usize = 8;
+
+/// Returns the levels of the A and B signals at the specified count of an
+/// encoder that turns forward, where A leads B. The levels form a Gray code,
+/// so only one signal changes between two counts.
+pub fn levels(count: i64) -> (bool, bool) {
+    return match count.rem_euclid(4) {
+        0 => (false, false),
+        1 => (true, false),
+        2 => (true, true),
+        _ => (false, true),
+    };
+}
+
+/// Decodes the A and B signals of a quadrature encoder. Every edge of either
+/// signal is a count, so an encoder with 12 lines gives 48 counts per
+/// revolution. A change of both signals at once means that an edge was
+/// missed, it is not counted but kept as an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quadrature {
+    /// the last levels of A and B, with A in the second bit
+    state: u8,
+    /// the number of counts, forward is positive
+    count: i64,
+    /// the number of transitions where both signals changed
+    errors: u32,
+}
+
+impl Quadrature {
+    /// Returns a new decoder that starts at zero with the specified levels.
+    pub fn new(a: bool, b: bool) -> Quadrature {
+        return Quadrature {
+            state: Quadrature::state(a, b),
+            count: 0,
+            errors: 0,
+        };
+    }
+
+    /// Updates the decoder with the current levels of A and B, and returns
+    /// the step that was counted: 1 forward, -1 backward or 0.
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let next = Quadrature::state(a, b);
+        let step = match (self.state, next) {
+            (0, 2) | (2, 3) | (3, 1) | (1, 0) => 1,
+            (0, 1) | (1, 3) | (3, 2) | (2, 0) => -1,
+            (s, n) if s == n => 0,
+            _ => {
+                self.errors += 1;
+                0
+            },
+        };
+
+        self.state = next;
+        self.count += step as i64;
+        return step;
+    }
+
+    /// Returns the number of counts, forward is positive.
+    pub fn count(&self) -> i64 {
+        return self.count;
+    }
+
+    /// Sets the number of counts, without changing the levels.
+    pub fn set_count(&mut self, count: i64) {
+        self.count = count;
+    }
+
+    /// Returns the number of transitions where an edge was missed.
+    pub fn errors(&self) -> u32 {
+        return self.errors;
+    }
+
+    /// Returns the state for the levels of A and B.
+    fn state(a: bool, b: bool) -> u8 {
+        return ((a as u8) << 1) | b as u8;
+    }
+}
+
+/// Estimates the velocity of an encoder from the times of its last counts.
+/// The times are passed in, so the estimate does not depend on a clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Velocity {
+    /// the time and count of the last counts, as a ring
+    samples: [(Duration, i64); WINDOW],
+    /// the number of samples in the ring
+    len: usize,
+    /// the index where the next sample is stored
+    next: usize,
+}
+
+impl Velocity {
+    /// Returns a new estimator without any samples.
+    pub fn new() -> Velocity {
+        return Velocity::default();
+    }
+
+    /// Records the count of an encoder at the specified time.
+    pub fn record(&mut self, at: Duration, count: i64) {
+        self.samples[self.next] = (at, count);
+        self.next = (self.next + 1) % WINDOW;
+        self.len = (self.len + 1).min(WINDOW);
+    }
+
+    /// Removes all samples.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the velocity in counts per second over the recorded window.
+    /// The velocity is zero when the encoder did not count for longer than
+    /// the timeout. Until then, it drops with the time since the last count,
+    /// so a motor that slows down is not reported at its old speed.
+    pub fn velocity(&self, now: Duration, timeout: Duration) -> f32 {
+        if self.len < 2 {
+            return 0.0;
+        }
+
+        let (first, c0) = self.samples[(self.next + WINDOW - self.len) % WINDOW];
+        let (last, c1) = self.samples[(self.next + WINDOW - 1) % WINDOW];
+        let since = now.saturating_sub(last);
+        let span = last.saturating_sub(first);
+        if since > timeout || span.is_zero() {
+            return 0.0;
+        }
+
+        let velocity = ((c1 - c0) as f64 / span.as_secs_f64()) as f32;
+        if since.is_zero() {
+            return velocity;
+        }
+
+        let limit = (1.0 / since.as_secs_f64()) as f32;
+        return velocity.signum() * velocity.abs().min(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_test() {
+        let mut q = Quadrature::new(false, false);
+        for count in 1..=10 {
+            let (a, b) = levels(count);
+            assert_eq!(1, q.update(a, b));
+        }
+        assert_eq!(10, q.count());
+
+        for count in (0..10).rev() {
+            let (a, b) = levels(count);
+            assert_eq!(-1, q.update(a, b));
+        }
+        assert_eq!(0, q.count());
+
+        // the same levels do not count, both levels at once is an error
+        assert_eq!(0, q.update(false, false));
+        assert_eq!(0, q.update(true, true));
+        assert_eq!(1, q.errors());
+        assert_eq!(0, q.count());
+    }
+
+    #[test]
+    fn velocity_test() {
+        let timeout = Duration::from_millis(100);
+        let mut v = Velocity::new();
+        assert_eq!(0.0, v.velocity(Duration::ZERO, timeout));
+
+        // a count every 2 ms is 500 counts per second
+        for i in 0..20 {
+            v.record(Duration::from_millis(2 * i), i as i64);
+        }
+        assert_eq!(500.0, v.velocity(Duration::from_millis(38), timeout));
+        assert_eq!(500.0, v.velocity(Duration::from_millis(39), timeout));
+
+        // without new counts the velocity drops and then stops
+        assert_eq!(100.0, v.velocity(Duration::from_millis(48), timeout));
+        assert_eq!(0.0, v.velocity(Duration::from_millis(139), timeout));
+
+        v.clear();
+        v.record(Duration::from_millis(0), 0);
+        v.record(Duration::from_millis(10), -5);
+        assert_eq!(-500.0, v.velocity(Duration::from_millis(10), timeout));
+    }
+}
@@ -0,0 +1,20 @@
+//! The hardware independent logic of the Arduino Motor Shield driver. This
+//! crate does not depend on the standard library, so the same logic can run
+//! on a microcontroller and on the Raspberry Pi.
+#![cfg_attr(not(test), no_std)]
+#![allow(clippy::needless_return)]
+
+pub mod debounce;
+pub mod directions;
+pub mod drive;
+pub mod encoder;
+pub mod motor;
+pub mod pca9685;
+pub mod pid;
+pub mod planner;
+pub mod pins;
+pub mod pwm;
+pub mod ramp;
+pub mod servo;
+pub mod shift_register;
+pub mod stepper;
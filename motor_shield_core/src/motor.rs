@@ -0,0 +1,130 @@
+use core::time::Duration;
+
+/// The direction in which a motor spins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Clockwise,
+    Counterclockwise
+}
+
+impl Direction {
+    /// Returns the opposite direction.
+    pub fn inverted(self) -> Direction {
+        return match self {
+            Direction::Clockwise => Direction::Counterclockwise,
+            Direction::Counterclockwise => Direction::Clockwise,
+        };
+    }
+}
+
+/// The way in which a motor is stopped. The L293D brakes a motor actively
+/// when both of its inputs are high while it is enabled, and lets the motor
+/// coast when both inputs are low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    Brake,
+    Release,
+}
+
+/// Motor configuration structure. The cycle and width of a pwm configuration
+/// are applied as a ratio to the period of the motor pin.
+#[derive(Debug, Clone, Copy)]
+pub struct MotorConfig {
+    cycle: Duration,
+    width: Duration,
+    full: bool
+}
+
+impl MotorConfig {
+    /// Returns a new config with the cycle and width set.
+    pub fn new_pwm(c: Duration, w: Duration) -> MotorConfig {
+        return MotorConfig {
+            cycle: c,
+            width: w,
+            full: false,
+        }
+    }
+
+    /// Rerturns a new config with the full property set to true.
+    pub fn new_full() -> MotorConfig {
+        return MotorConfig {
+            cycle: Duration::from_millis(100),
+            width: Duration::from_millis(100),
+            full: true,
+        }
+    }
+
+    /// Returns the pulse cycle of this config.
+    pub fn cycle(&self) -> Duration {
+        return self.cycle;
+    }
+
+    /// Returns the pulse width of this config.
+    pub fn width(&self) -> Duration {
+        return self.width;
+    }
+
+    /// Returns whether this config runs the motor at full speed.
+    pub fn is_full(&self) -> bool {
+        return self.full;
+    }
+
+    /// Returns the direction and the config for a signed speed from -1.0 to
+    /// 1.0. A positive speed runs clockwise, a negative speed
+    /// counterclockwise, and a speed of 1.0 or -1.0 runs at full speed. The
+    /// pulse width is the fraction of the specified cycle. A speed outside
+    /// the range or that is not a number returns `None`.
+    pub fn from_speed(speed: f32, cycle: Duration) -> Option<(Direction, MotorConfig)> {
+        if !speed.is_finite() || !(-1.0..=1.0).contains(&speed) {
+            return None;
+        }
+
+        let direction = match speed < 0.0 {
+            true => Direction::Counterclockwise,
+            false => Direction::Clockwise,
+        };
+
+        let magnitude = speed.abs();
+        if magnitude == 1.0 {
+            return Some((direction, MotorConfig::new_full()));
+        }
+
+        let width = Duration::from_nanos((cycle.as_nanos() as f64 * magnitude as f64) as u64);
+        return Some((direction, MotorConfig::new_pwm(cycle, width)));
+    }
+
+    /// Returns the fraction of the cycle that the motor is powered, from 0.0
+    /// to 1.0.
+    pub fn duty(&self) -> f32 {
+        if self.full {
+            return 1.0;
+        }
+
+        if self.cycle.is_zero() {
+            return 0.0;
+        }
+
+        return (self.width.as_secs_f64() / self.cycle.as_secs_f64()).min(1.0) as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_speed_test() {
+        let cycle = Duration::from_millis(1);
+        let (d, mc) = MotorConfig::from_speed(0.25, cycle).unwrap();
+        assert_eq!(Direction::Clockwise, d);
+        assert_eq!(Duration::from_micros(250), mc.width());
+        assert_eq!(0.25, mc.duty());
+
+        let (d, mc) = MotorConfig::from_speed(-1.0, cycle).unwrap();
+        assert_eq!(Direction::Counterclockwise, d);
+        assert!(mc.is_full());
+
+        assert!(MotorConfig::from_speed(1.5, cycle).is_none());
+        assert!(MotorConfig::from_speed(f32::NAN, cycle).is_none());
+    }
+}
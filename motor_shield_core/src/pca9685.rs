@@ -0,0 +1,245 @@
+use core::time::Duration;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// The mode register 1, which holds the sleep, auto increment and restart
+/// bits.
+pub const MODE1: u8 = 0x00;
+/// The mode register 2, which holds the output driver configuration.
+pub const MODE2: u8 = 0x01;
+/// The first register of channel 0. Every channel has four registers:
+/// ON_L, ON_H, OFF_L and OFF_H.
+pub const LED0_ON_L: u8 = 0x06;
+/// The prescaler of the pwm frequency. It can only be written while the
+/// oscillator is asleep.
+pub const PRESCALE: u8 = 0xFE;
+
+/// The restart bit of MODE1.
+pub const MODE1_RESTART: u8 = 0x80;
+/// The auto increment bit of MODE1. When it is set, every written byte
+/// moves on to the next register.
+pub const MODE1_AI: u8 = 0x20;
+/// The sleep bit of MODE1, which turns the oscillator off.
+pub const MODE1_SLEEP: u8 = 0x10;
+/// The totem pole output bit of MODE2.
+pub const MODE2_OUTDRV: u8 = 0x04;
+
+/// The frequency of the internal oscillator in Hz.
+pub const OSCILLATOR: u32 = 25_000_000;
+/// The number of steps of every pwm period. Setting bit 4 of the ON_H or
+/// OFF_H register, which is 4096 as a counter value, turns a channel fully
+/// on or off.
+pub const RESOLUTION: u16 = 4096;
+/// The number of channels of the chip.
+pub const CHANNELS: u8 = 16;
+/// The time in microseconds that the oscillator needs to stabilize after
+/// the sleep bit is cleared, before the restart bit may be set.
+pub const WAKE_DELAY_US: u32 = 500;
+
+/// Returns the prescaler for the specified pwm frequency in Hz, rounded to
+/// the nearest value. The chip supports frequencies from about 24 to 1526
+/// Hz, a frequency that rounds to a prescaler outside that range returns
+/// `None`.
+pub fn prescale_for(frequency: u32) -> Option<u8> {
+    if frequency == 0 {
+        return None;
+    }
+
+    let steps = RESOLUTION as u32 * frequency;
+    let prescale = (OSCILLATOR + steps / 2) / steps;
+    if !(4..=256).contains(&prescale) {
+        return None;
+    }
+
+    return Some((prescale - 1) as u8);
+}
+
+/// Returns the on and off counter values that make a channel run at the
+/// specified duty cycle out of `RESOLUTION`. A duty cycle of 0 turns the
+/// channel fully off and `RESOLUTION` or more turns it fully on.
+pub fn counts_for_duty(duty: u16) -> (u16, u16) {
+    if duty == 0 {
+        return (0, RESOLUTION);
+    }
+
+    if duty >= RESOLUTION {
+        return (RESOLUTION, 0);
+    }
+
+    return (0, duty);
+}
+
+/// Returns the number of counter steps that a pulse of the specified width
+/// takes with the specified prescaler, capped at `RESOLUTION`.
+pub fn counts_for_width(prescale: u8, width: Duration) -> u16 {
+    let step = (prescale as u128 + 1) * 1_000_000_000;
+    let counts = width.as_nanos() * OSCILLATOR as u128 / step;
+    return counts.min(RESOLUTION as u128) as u16;
+}
+
+/// A PCA9685 16 channel pwm driver on an I2C bus.
+pub struct Pca9685<I> {
+    /// The bus that the chip is on.
+    i2c: I,
+    /// The 7 bit address of the chip.
+    address: u8,
+}
+
+impl<I: I2c> Pca9685<I> {
+    /// Creates a new driver for the chip on the specified address. Nothing
+    /// is written to the chip until it is initialized.
+    pub fn new(i2c: I, address: u8) -> Pca9685<I> {
+        return Pca9685 { i2c, address };
+    }
+
+    /// Returns the address of the chip.
+    pub fn address(&self) -> u8 {
+        return self.address;
+    }
+
+    /// Returns the bus of the driver, consuming the driver.
+    pub fn release(self) -> I {
+        return self.i2c;
+    }
+
+    /// Initializes the chip by waking it up with auto increment enabled and
+    /// totem pole outputs.
+    pub fn init(&mut self) -> Result<(), Pca9685Error<I::Error>> {
+        self.write_register(MODE2, MODE2_OUTDRV)?;
+        self.write_register(MODE1, MODE1_AI)?;
+        return Ok(());
+    }
+
+    /// Sets the pwm frequency of all channels. The oscillator is put to
+    /// sleep while the prescaler is written, after which the chip restarts
+    /// with the previous channel outputs. The delay is used to give the
+    /// oscillator `WAKE_DELAY_US` to stabilize before the restart.
+    pub fn set_frequency<D: DelayNs>(&mut self, frequency: u32, delay: &mut D) -> Result<(), Pca9685Error<I::Error>> {
+        let prescale = prescale_for(frequency).ok_or(Pca9685Error::InvalidFrequency)?;
+        let mode = self.read_register(MODE1)? & !MODE1_RESTART;
+        self.write_register(MODE1, mode | MODE1_SLEEP)?;
+        self.write_register(PRESCALE, prescale)?;
+        self.write_register(MODE1, mode & !MODE1_SLEEP)?;
+        delay.delay_us(WAKE_DELAY_US);
+        self.write_register(MODE1, (mode & !MODE1_SLEEP) | MODE1_RESTART | MODE1_AI)?;
+        return Ok(());
+    }
+
+    /// Returns the prescaler that is currently set on the chip.
+    pub fn prescale(&mut self) -> Result<u8, Pca9685Error<I::Error>> {
+        return self.read_register(PRESCALE);
+    }
+
+    /// Sets the counter values at which the specified channel turns on and
+    /// off, using a single write of all four channel registers.
+    pub fn set_pwm(&mut self, channel: u8, on: u16, off: u16) -> Result<(), Pca9685Error<I::Error>> {
+        if channel >= CHANNELS {
+            return Err(Pca9685Error::InvalidChannel);
+        }
+
+        let register = LED0_ON_L + 4 * channel;
+        let bytes = [register, on as u8, (on >> 8) as u8, off as u8, (off >> 8) as u8];
+        self.i2c.write(self.address, &bytes).map_err(Pca9685Error::Bus)?;
+        return Ok(());
+    }
+
+    /// Sets the duty cycle of the specified channel out of `RESOLUTION`.
+    pub fn set_duty(&mut self, channel: u8, duty: u16) -> Result<(), Pca9685Error<I::Error>> {
+        let (on, off) = counts_for_duty(duty);
+        return self.set_pwm(channel, on, off);
+    }
+
+    /// Turns the specified channel fully on or fully off, which makes it
+    /// usable as a digital output.
+    pub fn set_pin(&mut self, channel: u8, high: bool) -> Result<(), Pca9685Error<I::Error>> {
+        return match high {
+            true => self.set_duty(channel, RESOLUTION),
+            false => self.set_duty(channel, 0),
+        };
+    }
+
+    /// Writes a single register of the chip.
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Pca9685Error<I::Error>> {
+        self.i2c.write(self.address, &[register, value]).map_err(Pca9685Error::Bus)?;
+        return Ok(());
+    }
+
+    /// Reads a single register of the chip.
+    fn read_register(&mut self, register: u8) -> Result<u8, Pca9685Error<I::Error>> {
+        let mut value = [0];
+        self.i2c.write_read(self.address, &[register], &mut value).map_err(Pca9685Error::Bus)?;
+        return Ok(value[0]);
+    }
+}
+
+#[derive(Debug)]
+pub enum Pca9685Error<E> {
+    InvalidChannel,
+    InvalidFrequency,
+    Bus(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    #[test]
+    fn prescale_test() {
+        assert_eq!(Some(121), prescale_for(50));
+        assert_eq!(Some(3), prescale_for(1526));
+        assert_eq!(Some(253), prescale_for(24));
+        assert_eq!(Some(3), prescale_for(1600));
+        assert_eq!(None, prescale_for(2000));
+        assert_eq!(None, prescale_for(20));
+        assert_eq!(None, prescale_for(0));
+    }
+
+    #[test]
+    fn counts_for_duty_test() {
+        assert_eq!((0, 4096), counts_for_duty(0));
+        assert_eq!((0, 2048), counts_for_duty(2048));
+        assert_eq!((4096, 0), counts_for_duty(4096));
+    }
+
+    #[test]
+    fn counts_for_width_test() {
+        assert_eq!(322, counts_for_width(121, Duration::from_micros(1575)));
+        assert_eq!(4096, counts_for_width(121, Duration::from_millis(30)));
+        assert_eq!(0, counts_for_width(121, Duration::ZERO));
+    }
+
+    #[test]
+    fn set_frequency_test() {
+        let expectations = [
+            Transaction::write_read(0x40, vec![MODE1], vec![MODE1_AI]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI | MODE1_SLEEP]),
+            Transaction::write(0x40, vec![PRESCALE, 121]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI | MODE1_RESTART]),
+        ];
+        let mut i2c = Mock::new(&expectations);
+        // the oscillator gets 500 us after waking up, before the restart
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_us(500)]);
+        let mut pca = Pca9685::new(i2c.clone(), 0x40);
+        pca.set_frequency(50, &mut delay).unwrap();
+        assert!(matches!(pca.set_frequency(2000, &mut delay), Err(Pca9685Error::InvalidFrequency)));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn set_pwm_test() {
+        let expectations = [
+            Transaction::write(0x60, vec![0x06 + 4 * 8, 0, 0, 0x00, 0x08]),
+            Transaction::write(0x60, vec![0x06 + 4 * 15, 0x00, 0x10, 0, 0]),
+        ];
+        let mut i2c = Mock::new(&expectations);
+        let mut pca = Pca9685::new(i2c.clone(), 0x60);
+        pca.set_duty(8, 2048).unwrap();
+        pca.set_pin(15, true).unwrap();
+        assert!(matches!(pca.set_pin(16, true), Err(Pca9685Error::InvalidChannel)));
+        i2c.done();
+    }
+}
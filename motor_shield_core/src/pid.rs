@@ -0,0 +1,165 @@
+/// A PID controller with output limits. The integral only grows while the
+/// output is not saturated in the direction of the error, so the controller
+/// does not wind up while the target can not be reached. The derivative is
+/// taken of the measurement instead of the error, so a change of the target
+/// does not kick the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pid {
+    /// the proportional gain
+    kp: f32,
+    /// the integral gain
+    ki: f32,
+    /// the derivative gain
+    kd: f32,
+    /// the lowest output
+    min: f32,
+    /// the highest output
+    max: f32,
+    /// the integral term, which already includes the integral gain
+    integral: f32,
+    /// the measurement of the previous update
+    last: Option<f32>,
+}
+
+impl Pid {
+    /// Returns a new controller with the specified gains and an output from
+    /// -1.0 to 1.0.
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Pid {
+        return Pid {
+            kp,
+            ki,
+            kd,
+            min: -1.0,
+            max: 1.0,
+            integral: 0.0,
+            last: None,
+        };
+    }
+
+    /// Sets the gains of the controller.
+    pub fn set_gains(&mut self, kp: f32, ki: f32, kd: f32) {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    /// Returns the proportional, integral and derivative gains.
+    pub fn gains(&self) -> (f32, f32, f32) {
+        return (self.kp, self.ki, self.kd);
+    }
+
+    /// Sets the lowest and the highest output. Returns false and keeps the
+    /// old limits if the lowest output is not below the highest.
+    pub fn set_limits(&mut self, min: f32, max: f32) -> bool {
+        if min.is_nan() || max.is_nan() || min >= max {
+            return false;
+        }
+
+        self.min = min;
+        self.max = max;
+        self.integral = self.integral.clamp(min, max);
+        return true;
+    }
+
+    /// Returns the lowest and the highest output.
+    pub fn limits(&self) -> (f32, f32) {
+        return (self.min, self.max);
+    }
+
+    /// Clears the integral and the previous measurement.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last = None;
+    }
+
+    /// Returns the output for the measurement, when the previous update was
+    /// the specified number of seconds ago.
+    pub fn update(&mut self, target: f32, measured: f32, dt: f32) -> f32 {
+        let error = target - measured;
+        let derivative = match self.last {
+            Some(last) if dt > 0.0 => (last - measured) / dt,
+            _ => 0.0,
+        };
+        self.last = Some(measured);
+
+        let proportional = self.kp * error + self.kd * derivative;
+        let integral = self.integral + self.ki * error * dt;
+        let output = proportional + integral;
+        let winding = (output > self.max && error > 0.0) || (output < self.min && error < 0.0);
+        if !winding {
+            self.integral = integral.clamp(self.min, self.max);
+        }
+
+        return (proportional + self.integral).clamp(self.min, self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A motor that reaches 1000 counts per second at full duty, with a time
+    /// constant of 100 ms.
+    struct Plant {
+        speed: f32,
+    }
+
+    impl Plant {
+        fn step(&mut self, duty: f32, dt: f32) -> f32 {
+            self.speed += (1000.0 * duty - self.speed) * dt / 0.1;
+            return self.speed;
+        }
+    }
+
+    #[test]
+    fn reaches_target_test() {
+        let mut pid = Pid::new(0.001, 0.01, 0.0);
+        let mut plant = Plant { speed: 0.0 };
+        let mut duty = 0.0;
+        for _ in 0..300 {
+            let speed = plant.step(duty, 0.01);
+            duty = pid.update(600.0, speed, 0.01);
+        }
+        assert!((plant.speed - 600.0).abs() < 5.0, "speed {:?}", plant.speed);
+        assert!((duty - 0.6).abs() < 0.01, "duty {:?}", duty);
+    }
+
+    #[test]
+    fn anti_windup_test() {
+        let mut pid = Pid::new(0.001, 0.01, 0.0);
+        let mut plant = Plant { speed: 0.0 };
+        let mut duty = 0.0;
+
+        // the target can not be reached, the output stays at the limit
+        for _ in 0..1000 {
+            let speed = plant.step(duty, 0.01);
+            duty = pid.update(2000.0, speed, 0.01);
+        }
+        assert_eq!(1.0, duty);
+
+        // the output drops right away, because the integral did not wind up
+        let speed = plant.step(duty, 0.01);
+        duty = pid.update(500.0, speed, 0.01);
+        assert!(duty < 1.0, "duty {:?}", duty);
+        for _ in 0..100 {
+            let speed = plant.step(duty, 0.01);
+            duty = pid.update(500.0, speed, 0.01);
+        }
+        assert!(plant.speed < 600.0, "speed {:?}", plant.speed);
+    }
+
+    #[test]
+    fn limits_test() {
+        let mut pid = Pid::new(1.0, 0.0, 0.0);
+        assert!(pid.set_limits(0.0, 0.5));
+        assert!(!pid.set_limits(0.5, 0.5));
+        assert_eq!((0.0, 0.5), pid.limits());
+        assert_eq!(0.5, pid.update(10.0, 0.0, 0.01));
+        assert_eq!(0.0, pid.update(-10.0, 0.0, 0.01));
+
+        // the derivative is taken of the measurement
+        let mut pid = Pid::new(0.0, 0.0, 0.01);
+        assert_eq!(0.0, pid.update(1.0, 0.0, 0.01));
+        assert_eq!(-1.0, pid.update(1.0, 1.0, 0.01));
+    }
+}
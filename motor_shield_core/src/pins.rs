@@ -0,0 +1,83 @@
+/// The BCM numbers of the pins on the 40 pin header of the Raspberry Pi,
+/// indexed by the physical pin number minus one. Power and ground pins have
+/// no BCM number.
+const HEADER: [Header; 40] = [
+    Header::Power, Header::Power,
+    Header::Gpio(2), Header::Power,
+    Header::Gpio(3), Header::Ground,
+    Header::Gpio(4), Header::Gpio(14),
+    Header::Ground, Header::Gpio(15),
+    Header::Gpio(17), Header::Gpio(18),
+    Header::Gpio(27), Header::Ground,
+    Header::Gpio(22), Header::Gpio(23),
+    Header::Power, Header::Gpio(24),
+    Header::Gpio(10), Header::Ground,
+    Header::Gpio(9), Header::Gpio(25),
+    Header::Gpio(11), Header::Gpio(8),
+    Header::Ground, Header::Gpio(7),
+    Header::Gpio(0), Header::Gpio(1),
+    Header::Gpio(5), Header::Ground,
+    Header::Gpio(6), Header::Gpio(12),
+    Header::Gpio(13), Header::Ground,
+    Header::Gpio(19), Header::Gpio(16),
+    Header::Gpio(26), Header::Gpio(20),
+    Header::Ground, Header::Gpio(21),
+];
+
+#[derive(Clone, Copy)]
+enum Header {
+    Power,
+    Ground,
+    Gpio(u8),
+}
+
+/// Returns the BCM number of the gpio on the specified physical pin of the
+/// 40 pin header. The physical pins are numbered 1 to 40, with odd numbers
+/// on the inner row.
+pub fn bcm_from_header(pin: u8) -> Result<u8, PinError> {
+    if !(1..=40).contains(&pin) {
+        return Err(PinError::NotOnHeader(pin));
+    }
+
+    return match HEADER[pin as usize - 1] {
+        Header::Power => Err(PinError::Power(pin)),
+        Header::Ground => Err(PinError::Ground(pin)),
+        Header::Gpio(bcm) => Ok(bcm),
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinError {
+    NotOnHeader(u8),
+    Power(u8),
+    Ground(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bcm_from_header_test() {
+        assert_eq!(Ok(2), bcm_from_header(3));
+        assert_eq!(Ok(16), bcm_from_header(36));
+        assert_eq!(Ok(21), bcm_from_header(40));
+        assert_eq!(Err(PinError::Power(1)), bcm_from_header(1));
+        assert_eq!(Err(PinError::Power(17)), bcm_from_header(17));
+        assert_eq!(Err(PinError::Ground(39)), bcm_from_header(39));
+        assert_eq!(Err(PinError::NotOnHeader(0)), bcm_from_header(0));
+        assert_eq!(Err(PinError::NotOnHeader(41)), bcm_from_header(41));
+    }
+
+    #[test]
+    fn every_gpio_once_test() {
+        let mut seen = [false; 28];
+        for pin in 1..=40 {
+            if let Ok(bcm) = bcm_from_header(pin) {
+                assert!(!seen[bcm as usize]);
+                seen[bcm as usize] = true;
+            }
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+}
@@ -0,0 +1,364 @@
+use core::time::Duration;
+
+/// # A trapezoidal motion planner for stepper motors
+///
+/// The planner computes the time between the steps of a move to a target
+/// position, so the motor accelerates up to the maximum speed, cruises, and
+/// decelerates to stand still on the target. It follows the algorithm of
+/// AccelStepper by David Austin, which computes every interval from the
+/// previous one without a square root. Positions are in steps and speeds in
+/// steps per second, forward is positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Planner {
+    /// the current position
+    position: i64,
+    /// the position that the motor moves to
+    target: i64,
+    /// the current speed, negative backward
+    speed: f32,
+    /// the highest speed
+    max_speed: f32,
+    /// the acceleration and deceleration in steps per second squared
+    acceleration: f32,
+    /// the number of the step on the ramp, negative while decelerating
+    n: i64,
+    /// the interval of the first step in seconds
+    c0: f32,
+    /// the interval of the last step in seconds
+    cn: f32,
+    /// the interval at the highest speed in seconds
+    cmin: f32,
+    /// the direction of the next step, 1 forward and -1 backward
+    direction: i64,
+    /// the interval until the next step in seconds, zero while standing still
+    interval: f32,
+}
+
+impl Planner {
+    /// Returns a new planner at position zero with the specified maximum
+    /// speed and acceleration, or `None` if either is not above zero.
+    pub fn new(max_speed: f32, acceleration: f32) -> Option<Planner> {
+        let mut planner = Planner {
+            position: 0,
+            target: 0,
+            speed: 0.0,
+            max_speed: 1.0,
+            acceleration: 1.0,
+            n: 0,
+            c0: 0.0,
+            cn: 0.0,
+            cmin: 1.0,
+            direction: 1,
+            interval: 0.0,
+        };
+
+        if !planner.set_max_speed(max_speed) || !planner.set_acceleration(acceleration) {
+            return None;
+        }
+
+        return Some(planner);
+    }
+
+    /// Sets the highest speed in steps per second. Returns false and keeps
+    /// the old speed if it is not above zero. A motor that is faster
+    /// decelerates to the new speed.
+    pub fn set_max_speed(&mut self, max_speed: f32) -> bool {
+        if !max_speed.is_finite() || max_speed <= 0.0 {
+            return false;
+        }
+
+        self.max_speed = max_speed;
+        self.cmin = 1.0 / max_speed;
+        if self.n > 0 {
+            self.n = self.steps_to_stop();
+            self.compute_speed();
+        }
+
+        return true;
+    }
+
+    /// Returns the highest speed in steps per second.
+    pub fn max_speed(&self) -> f32 {
+        return self.max_speed;
+    }
+
+    /// Sets the acceleration in steps per second squared. Returns false and
+    /// keeps the old acceleration if it is not above zero.
+    pub fn set_acceleration(&mut self, acceleration: f32) -> bool {
+        if !acceleration.is_finite() || acceleration <= 0.0 {
+            return false;
+        }
+
+        // keep the speed, at the step of the new ramp with the same speed
+        self.n = (self.n as f32 * self.acceleration / acceleration) as i64;
+        self.acceleration = acceleration;
+        self.c0 = 0.676 * sqrt(2.0 / acceleration);
+        self.compute_speed();
+        return true;
+    }
+
+    /// Returns the acceleration in steps per second squared.
+    pub fn acceleration(&self) -> f32 {
+        return self.acceleration;
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> i64 {
+        return self.position;
+    }
+
+    /// Sets the current position and the target, and stops the motor at once.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position;
+        self.target = position;
+        self.speed = 0.0;
+        self.n = 0;
+        self.interval = 0.0;
+    }
+
+    /// Returns the position that the motor moves to.
+    pub fn target(&self) -> i64 {
+        return self.target;
+    }
+
+    /// Sets the position that the motor moves to. A moving motor first
+    /// decelerates when the target is behind it or too close to stop on.
+    pub fn move_to(&mut self, target: i64) {
+        if self.target != target {
+            self.target = target;
+            self.compute_speed();
+        }
+    }
+
+    /// Sets the target relative to the current position.
+    pub fn move_by(&mut self, distance: i64) {
+        self.move_to(self.position + distance);
+    }
+
+    /// Sets the target to the nearest position that the motor can stop on
+    /// with the acceleration, so it decelerates to stand still.
+    pub fn stop(&mut self) {
+        if self.speed == 0.0 {
+            return;
+        }
+
+        let steps = self.steps_to_stop() + 1;
+        match self.speed > 0.0 {
+            true => self.move_to(self.position + steps),
+            false => self.move_to(self.position - steps),
+        };
+    }
+
+    /// Returns the number of steps from the current position to the target.
+    pub fn distance_to_go(&self) -> i64 {
+        return self.target - self.position;
+    }
+
+    /// Returns the current speed in steps per second, negative backward.
+    pub fn speed(&self) -> f32 {
+        return self.speed;
+    }
+
+    /// Returns whether the motor is moving or has not reached the target.
+    pub fn is_running(&self) -> bool {
+        return self.speed != 0.0 || self.distance_to_go() != 0;
+    }
+
+    /// Returns the time between the previous step and the next step, or
+    /// `None` if the motor stands still on the target.
+    pub fn step_interval(&self) -> Option<Duration> {
+        if self.interval <= 0.0 {
+            return None;
+        }
+
+        return Some(Duration::from_secs_f32(self.interval));
+    }
+
+    /// Makes the next step and plans the one after it. Returns the direction
+    /// of the step, 1 forward and -1 backward, or 0 if the motor stands
+    /// still on the target.
+    pub fn step(&mut self) -> i64 {
+        if self.interval <= 0.0 {
+            return 0;
+        }
+
+        let direction = self.direction;
+        self.position += direction;
+        self.compute_speed();
+        return direction;
+    }
+
+    /// Returns the number of steps that the motor needs to stop from the
+    /// current speed.
+    fn steps_to_stop(&self) -> i64 {
+        return ((self.speed * self.speed) / (2.0 * self.acceleration)) as i64;
+    }
+
+    /// Computes the speed and the interval of the next step from the
+    /// distance to the target.
+    fn compute_speed(&mut self) {
+        let distance = self.distance_to_go();
+        let stopping = self.steps_to_stop();
+
+        if distance == 0 && stopping <= 1 {
+            self.interval = 0.0;
+            self.speed = 0.0;
+            self.n = 0;
+            return;
+        }
+
+        if distance > 0 {
+            // decelerate when the target is too close or behind the motor
+            if self.n > 0 && (stopping >= distance || self.direction < 0) {
+                self.n = -stopping;
+            } else if self.n < 0 && stopping < distance && self.direction > 0 {
+                self.n = -self.n;
+            }
+        } else if distance < 0 {
+            if self.n > 0 && (stopping >= -distance || self.direction > 0) {
+                self.n = -stopping;
+            } else if self.n < 0 && stopping < -distance && self.direction < 0 {
+                self.n = -self.n;
+            }
+        }
+
+        if self.n == 0 {
+            // the first step from standstill
+            self.cn = self.c0;
+            self.direction = match distance > 0 {
+                true => 1,
+                false => -1,
+            };
+        } else {
+            self.cn -= (2.0 * self.cn) / ((4 * self.n) as f32 + 1.0);
+            self.cn = self.cn.max(self.cmin);
+        }
+        self.n += 1;
+
+        self.interval = self.cn;
+        self.speed = self.direction as f32 / self.cn;
+    }
+}
+
+/// Returns the square root of a number that is above zero, by Newton's
+/// method, since the core does not have the math library.
+fn sqrt(x: f32) -> f32 {
+    let mut root = if x > 1.0 { x } else { 1.0 };
+    for _ in 0..64 {
+        let next = 0.5 * (root + x / root);
+        if next >= root {
+            break;
+        }
+        root = next;
+    }
+
+    return root;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the planner until the motor stands still and returns the
+    /// intervals of the steps in seconds, with the direction of every step.
+    fn run(planner: &mut Planner) -> Vec<(i64, f32)> {
+        let mut steps = Vec::new();
+        while let Some(interval) = planner.step_interval() {
+            let direction = planner.step();
+            steps.push((direction, interval.as_secs_f32()));
+            assert!(steps.len() < 100_000);
+        }
+
+        return steps;
+    }
+
+    #[test]
+    fn sqrt_test() {
+        for x in [0.0001, 0.02, 1.0, 2.0, 1000.0, 1.0e6] {
+            let root: f32 = sqrt(x);
+            assert!((root * root - x).abs() <= x * 1e-5, "sqrt {:?} = {:?}", x, root);
+        }
+    }
+
+    #[test]
+    fn trapezoid_test() {
+        let mut planner = Planner::new(1000.0, 2000.0).unwrap();
+        planner.move_to(2000);
+        let steps = run(&mut planner);
+        assert_eq!(2000, steps.len());
+        assert!(steps.iter().all(|(d, _)| *d == 1));
+        assert_eq!(2000, planner.position());
+        assert_eq!(0.0, planner.speed());
+        assert!(!planner.is_running());
+
+        // the motor accelerates, cruises at the highest speed, and decelerates
+        let intervals: Vec<f32> = steps.iter().map(|(_, i)| *i).collect();
+        assert!((intervals[0] - 0.676 * (2.0f32 / 2000.0).sqrt()).abs() < 1e-6);
+        assert!(intervals[..100].windows(2).all(|w| w[1] <= w[0]));
+        assert!(intervals[1900..].windows(2).all(|w| w[1] >= w[0]));
+        assert!((intervals[1000] - 0.001).abs() < 1e-7);
+
+        // about v² / 2a = 250 steps to reach the highest speed
+        let ramp = intervals.iter().position(|i| *i <= 0.001).unwrap();
+        assert!((230..270).contains(&ramp), "ramp {:?}", ramp);
+
+        // the move takes about 2000 / 1000 + 1000 / 2000 seconds
+        let total: f32 = intervals.iter().sum();
+        assert!((2.4..2.6).contains(&total), "total {:?}", total);
+    }
+
+    #[test]
+    fn triangle_and_backward_test() {
+        // too short to reach the highest speed
+        let mut planner = Planner::new(1000.0, 1000.0).unwrap();
+        planner.move_by(-100);
+        let steps = run(&mut planner);
+        assert_eq!(100, steps.len());
+        assert!(steps.iter().all(|(d, _)| *d == -1));
+        let fastest = steps.iter().map(|(_, i)| *i).fold(f32::MAX, f32::min);
+        assert!(fastest > 0.003, "fastest {:?}", fastest);
+        assert_eq!(-100, planner.position());
+    }
+
+    #[test]
+    fn stop_decelerates_test() {
+        let mut planner = Planner::new(1000.0, 2000.0).unwrap();
+        planner.move_to(10_000);
+        while planner.speed() < 999.0 {
+            planner.step();
+        }
+
+        // 1000² / (2 * 2000) = 250 steps to stop
+        let position = planner.position();
+        planner.stop();
+        assert!((250..=251).contains(&(planner.target() - position)));
+        let steps = run(&mut planner);
+        assert!(steps.len() <= 251 && steps.len() > 240, "steps {:?}", steps.len());
+        assert!(steps.iter().all(|(d, _)| *d == 1));
+        assert!(!planner.is_running());
+    }
+
+    #[test]
+    fn reverse_test() {
+        // a target behind a moving motor decelerates before turning around
+        let mut planner = Planner::new(500.0, 1000.0).unwrap();
+        planner.move_to(1000);
+        for _ in 0..200 {
+            planner.step();
+        }
+        planner.move_to(0);
+        let steps = run(&mut planner);
+        let forward = steps.iter().take_while(|(d, _)| *d == 1).count();
+        assert!(forward > 50, "forward {:?}", forward);
+        assert!(steps[forward..].iter().all(|(d, _)| *d == -1));
+        assert_eq!(0, planner.position());
+
+        // invalid limits are refused
+        assert!(Planner::new(0.0, 1.0).is_none());
+        assert!(!planner.set_acceleration(f32::NAN));
+        assert!(planner.set_max_speed(200.0));
+        planner.set_position(42);
+        assert_eq!(None, planner.step_interval());
+        assert_eq!(0, planner.step());
+    }
+}
@@ -0,0 +1,34 @@
+use core::time::Duration;
+use embedded_hal::pwm::SetDutyCycle;
+
+/// Returns the duty cycle that makes the pulse width relate to the period as
+/// the specified `width` relates to `cycle`, scaled to `max`. A width that is
+/// longer than the cycle results in the maximum duty cycle.
+pub fn duty_from_width(max: u16, cycle: Duration, width: Duration) -> u16 {
+    if cycle.is_zero() {
+        return 0;
+    }
+
+    return (max as u128 * width.as_nanos() / cycle.as_nanos()).min(max as u128) as u16;
+}
+
+/// Sets the duty cycle of a pwm pin so that the pulse width relates to the
+/// period as the specified `width` relates to `cycle`. The period of the
+/// signal itself is the period of the pin.
+pub fn set_pulse_width<P: SetDutyCycle>(pin: &mut P, cycle: Duration, width: Duration) -> Result<(), P::Error> {
+    let duty = duty_from_width(pin.max_duty_cycle(), cycle, width);
+    return pin.set_duty_cycle(duty);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_from_width_test() {
+        let cycle = Duration::from_millis(20);
+        assert_eq!(500, duty_from_width(1000, cycle, Duration::from_millis(10)));
+        assert_eq!(1000, duty_from_width(1000, cycle, Duration::from_millis(30)));
+        assert_eq!(0, duty_from_width(1000, Duration::ZERO, Duration::from_millis(10)));
+    }
+}
@@ -0,0 +1,41 @@
+/// Returns the speed that is one step of at most `max_step` closer to the
+/// target speed. Speeds are signed, from -1.0 to 1.0. When the target has the
+/// other sign than the current speed, the ramp first stops at zero, so the
+/// direction of the motor is only switched while it is stopped.
+pub fn step_towards(current: f32, target: f32, max_step: f32) -> f32 {
+    let mut goal = target;
+    if current != 0.0 && goal != 0.0 && (current < 0.0) != (goal < 0.0) {
+        goal = 0.0;
+    }
+
+    let delta = goal - current;
+    if delta.abs() <= max_step {
+        return goal;
+    }
+
+    return match delta > 0.0 {
+        true => current + max_step,
+        false => current - max_step,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_towards_test() {
+        assert_eq!(0.25, step_towards(0.0, 1.0, 0.25));
+        assert_eq!(0.5, step_towards(0.25, 0.5, 0.25));
+        assert_eq!(0.5, step_towards(0.6, 0.5, 0.25));
+        assert_eq!(-0.25, step_towards(0.0, -1.0, 0.25));
+    }
+
+    #[test]
+    fn step_towards_crosses_zero_test() {
+        assert_eq!(0.0, step_towards(0.2, -0.5, 0.25));
+        assert_eq!(0.25, step_towards(0.5, -0.5, 0.25));
+        assert_eq!(-0.25, step_towards(0.0, -0.5, 0.25));
+        assert_eq!(0.0, step_towards(-0.1, 0.5, 0.25));
+    }
+}
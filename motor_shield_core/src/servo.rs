@@ -0,0 +1,75 @@
+use core::time::Duration;
+
+/// The minimum pulse width of the servo in microseconds, used to calculate
+/// angles.
+pub const MIN: f32 = 600.0;
+/// The maximum pulse width of the servo in microseconds, used to calculate
+/// angles.
+pub const MAX: f32 = 2550.0;
+/// The maximum angle that the servo supports.
+pub const MAX_ANGLE: u16 = 180;
+/// The pulse cycle of the servo. This is made for servos that use a
+/// frequency of 50 Herz or 20 milliseconds.
+pub const PULSE_CYCLE: Duration = Duration::from_millis(20);
+
+/// Returns the duration of a pulse width calculated from the specified
+/// angle.
+pub fn width_from_angle(a: u16) -> Duration {
+    if a > MAX_ANGLE {
+        panic!("angle can be at most 180");
+    }
+
+    // the range over which the servo can operate
+    let range: f32 = MAX - MIN;
+    // the percentage of width over the total range
+    let prcnt: f32 = (a as f32) / (MAX_ANGLE as f32);
+    // the scalar that should go on top of the minimum value
+    let scalar: f32 = prcnt * range;
+    // the final width of the pulse
+    let width: u64 = (MIN as u64) + (scalar as u64);
+
+    return Duration::from_micros(width);
+}
+
+/// Returns the duration of a pulse width calculated from the specified
+/// angle, for a servo that is calibrated to reach 0 degrees at `min` and
+/// 180 degrees at `max`.
+pub fn width_from_angle_between(min: Duration, max: Duration, a: u16) -> Duration {
+    if a > MAX_ANGLE {
+        panic!("angle can be at most 180");
+    }
+
+    let range = max.saturating_sub(min).as_nanos();
+    let scalar = range * a as u128 / MAX_ANGLE as u128;
+
+    return min + Duration::from_nanos(scalar as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_from_angle_test() {
+        assert_eq!(Duration::from_micros(600), width_from_angle(0));
+        assert_eq!(Duration::from_micros(1575), width_from_angle(90));
+        assert_eq!(Duration::from_micros(2550), width_from_angle(180));
+    }
+
+    #[test]
+    fn width_from_angle_between_test() {
+        let min = Duration::from_micros(500);
+        let max = Duration::from_micros(2500);
+        assert_eq!(min, width_from_angle_between(min, max, 0));
+        assert_eq!(Duration::from_micros(1500), width_from_angle_between(min, max, 90));
+        assert_eq!(max, width_from_angle_between(min, max, 180));
+        let (min, max) = (Duration::from_micros(MIN as u64), Duration::from_micros(MAX as u64));
+        assert_eq!(width_from_angle(90), width_from_angle_between(min, max, 90));
+    }
+
+    #[test]
+    #[should_panic]
+    fn width_from_angle_out_of_range_test() {
+        width_from_angle(181);
+    }
+}
@@ -0,0 +1,221 @@
+use embedded_hal::digital::OutputPin;
+
+/// Pushes a byte onto a 74HC595 shift register and latches it onto the
+/// outputs. The latch is set low before pushing, then every bit is pushed
+/// from MSB to LSB on a rising edge of the clock. Once all bits are pushed,
+/// the latch is set high and the byte is stored into the memory.
+pub fn push_byte<P: OutputPin>(serial: &mut P, clock: &mut P, latch: &mut P, byte: u8) -> Result<(), P::Error> {
+    latch.set_low()?; // set the latch low before pushing
+    shift_byte(serial, clock, byte)?;
+    latch.set_high()?; // once all bits are pushed, store in memory by opening the latch
+
+    return Ok(());
+}
+
+/// Shifts a byte into the shift register without touching the latch. The
+/// bits are pushed from MSB to LSB.
+pub fn shift_byte<P: OutputPin>(serial: &mut P, clock: &mut P, byte: u8) -> Result<(), P::Error> {
+    let mut b: u16 = 128; // initiate the MSB as the first bit to be pushed
+    while b != 0 { // continue as long as we still have to push a bit
+        clock.set_low()?; // set the clock low, we need a rising edge to push
+        let c: u16 = b & (byte as u16); // bitwise AND with the bit to push
+        if c == b { // if the byte had a 1 on that bit, it is still 1
+            serial.set_high()?; // this means that this push should be high
+        } else { // otherwise it was a 0
+            serial.set_low()?; // so we push a low
+        }
+        clock.set_high()?; // set the clock high, a rising edge will push the data
+        b >>= 1; // shift the bit to push to the right
+    }
+
+    return Ok(());
+}
+
+/// A 74HC595 shift register, or a chain of them. Registers can be
+/// daisy-chained by connecting the QH' output of one register to the serial
+/// input of the next one, while all registers share the clock and latch
+/// lines. The output enable and clear pins are optional, since they are
+/// often tied to ground and 5V.
+pub struct ShiftRegister<P> {
+    /// The serial pin, DIR_SER on the motor shield.
+    serial: P,
+    /// The shift register clock pin, DIR_CLK on the motor shield.
+    clock: P,
+    /// The register clock pin, DIR_LATCH on the motor shield.
+    latch: P,
+    /// The output enable pin, which is active low.
+    output_enable: Option<P>,
+    /// The shift register clear pin, which is active low.
+    clear: Option<P>,
+}
+
+impl<P: OutputPin> ShiftRegister<P> {
+    /// Creates a new shift register from the serial, clock and latch pins.
+    pub fn new(serial: P, clock: P, latch: P) -> ShiftRegister<P> {
+        return ShiftRegister {
+            serial,
+            clock,
+            latch,
+            output_enable: None,
+            clear: None,
+        };
+    }
+
+    /// Sets the output enable pin of the register. The pin is left as it is,
+    /// so the outputs are not changed until they are enabled or disabled.
+    pub fn set_output_enable(&mut self, pin: P) {
+        self.output_enable = Some(pin);
+    }
+
+    /// Sets the clear pin of the register. The pin is set high, so the
+    /// register is not held in its cleared state.
+    pub fn set_clear(&mut self, pin: P) -> Result<(), P::Error> {
+        let mut pin = pin;
+        pin.set_high()?;
+        self.clear = Some(pin);
+        return Ok(());
+    }
+
+    /// Returns whether an output enable pin is set.
+    pub fn has_output_enable(&self) -> bool {
+        return self.output_enable.is_some();
+    }
+
+    /// Pushes a byte onto a single register and latches it onto the outputs.
+    pub fn push_byte(&mut self, byte: u8) -> Result<(), P::Error> {
+        return push_byte(&mut self.serial, &mut self.clock, &mut self.latch, byte);
+    }
+
+    /// Pushes a byte for every register in the chain and latches them all at
+    /// once. The first byte ends up in the first register of the chain, the
+    /// one that is connected to the serial pin, so the bytes are shifted in
+    /// reverse order.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<(), P::Error> {
+        self.latch.set_low()?; // set the latch low before pushing
+        for byte in bytes.iter().rev() {
+            shift_byte(&mut self.serial, &mut self.clock, *byte)?;
+        }
+        self.latch.set_high()?; // store all bytes in memory at once
+
+        return Ok(());
+    }
+
+    /// Enables the outputs of the register by setting the output enable pin
+    /// low.
+    pub fn enable_outputs(&mut self) -> Result<(), ShiftRegisterError<P::Error>> {
+        let pin = self.output_enable.as_mut().ok_or(ShiftRegisterError::PinNotSet)?;
+        pin.set_low().map_err(ShiftRegisterError::Pin)?;
+        return Ok(());
+    }
+
+    /// Disables the outputs of the register by setting the output enable pin
+    /// high. The outputs float, but the latched bytes are kept.
+    pub fn disable_outputs(&mut self) -> Result<(), ShiftRegisterError<P::Error>> {
+        let pin = self.output_enable.as_mut().ok_or(ShiftRegisterError::PinNotSet)?;
+        pin.set_high().map_err(ShiftRegisterError::Pin)?;
+        return Ok(());
+    }
+
+    /// Clears the register by pulsing the clear pin low, and latches the
+    /// cleared register onto the outputs.
+    pub fn clear(&mut self) -> Result<(), ShiftRegisterError<P::Error>> {
+        let pin = self.clear.as_mut().ok_or(ShiftRegisterError::PinNotSet)?;
+        pin.set_low().map_err(ShiftRegisterError::Pin)?;
+        pin.set_high().map_err(ShiftRegisterError::Pin)?;
+        self.latch.set_low().map_err(ShiftRegisterError::Pin)?;
+        self.latch.set_high().map_err(ShiftRegisterError::Pin)?;
+        return Ok(());
+    }
+}
+
+#[derive(Debug)]
+pub enum ShiftRegisterError<E> {
+    PinNotSet,
+    Pin(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::digital::{Mock, State, Transaction};
+
+    #[test]
+    fn push_byte_test() {
+        // 0b10000001 is pushed from MSB to LSB
+        let mut serial_expectations = vec![Transaction::set(State::High)];
+        serial_expectations.extend(vec![Transaction::set(State::Low); 6]);
+        serial_expectations.push(Transaction::set(State::High));
+        let mut clock_expectations = Vec::new();
+        for _ in 0..8 {
+            clock_expectations.push(Transaction::set(State::Low));
+            clock_expectations.push(Transaction::set(State::High));
+        }
+        let latch_expectations = [Transaction::set(State::Low), Transaction::set(State::High)];
+
+        let mut serial = Mock::new(&serial_expectations);
+        let mut clock = Mock::new(&clock_expectations);
+        let mut latch = Mock::new(&latch_expectations);
+        push_byte(&mut serial, &mut clock, &mut latch, 0b10000001).unwrap();
+
+        serial.done();
+        clock.done();
+        latch.done();
+    }
+
+    fn clock_pulses(n: usize) -> Vec<Transaction> {
+        let mut expectations = Vec::new();
+        for _ in 0..n {
+            expectations.push(Transaction::set(State::Low));
+            expectations.push(Transaction::set(State::High));
+        }
+        return expectations;
+    }
+
+    #[test]
+    fn push_daisy_chain_test() {
+        // the second byte 0b00000001 is shifted first, then 0b10000000
+        let mut serial_expectations = vec![Transaction::set(State::Low); 7];
+        serial_expectations.push(Transaction::set(State::High));
+        serial_expectations.push(Transaction::set(State::High));
+        serial_expectations.extend(vec![Transaction::set(State::Low); 7]);
+        let latch_expectations = [Transaction::set(State::Low), Transaction::set(State::High)];
+
+        let mut serial = Mock::new(&serial_expectations);
+        let mut clock = Mock::new(&clock_pulses(16));
+        let mut latch = Mock::new(&latch_expectations);
+        let mut register = ShiftRegister::new(serial.clone(), clock.clone(), latch.clone());
+        register.push(&[0b10000000, 0b00000001]).unwrap();
+
+        serial.done();
+        clock.done();
+        latch.done();
+    }
+
+    #[test]
+    fn output_enable_and_clear_test() {
+        let mut serial = Mock::new(&[]);
+        let mut clock = Mock::new(&[]);
+        let mut latch = Mock::new(&[Transaction::set(State::Low), Transaction::set(State::High)]);
+        let mut oe = Mock::new(&[Transaction::set(State::High), Transaction::set(State::Low)]);
+        let clear_expectations = [
+            Transaction::set(State::High),
+            Transaction::set(State::Low),
+            Transaction::set(State::High),
+        ];
+        let mut clear = Mock::new(&clear_expectations);
+
+        let mut register = ShiftRegister::new(serial.clone(), clock.clone(), latch.clone());
+        assert!(matches!(register.disable_outputs(), Err(ShiftRegisterError::PinNotSet)));
+        register.set_output_enable(oe.clone());
+        register.set_clear(clear.clone()).unwrap();
+        register.disable_outputs().unwrap();
+        register.enable_outputs().unwrap();
+        register.clear().unwrap();
+
+        serial.done();
+        clock.done();
+        latch.done();
+        oe.done();
+        clear.done();
+    }
+}
@@ -0,0 +1,201 @@
+use crate::directions::SlotState;
+use crate::motor::{Direction, StopMode};
+use core::time::Duration;
+
+/// The number of phase units in one full step. The position of a stepper in
+/// its coil sequence is kept in these units, which is the resolution of the
+/// finest microstepping.
+pub const PHASES_PER_STEP: i64 = 16;
+
+/// The sine of a quarter wave in 16 parts, `SINE[k]` is `sin(k * pi / 32)`.
+/// The current through the coils follows this table when microstepping.
+pub const SINE: [f32; 17] = [
+    0.0, 0.09801714, 0.19509032, 0.29028466, 0.38268343, 0.47139674,
+    0.55557024, 0.6343933, core::f32::consts::FRAC_1_SQRT_2, 0.77301043,
+    0.8314696, 0.8819213, 0.9238795, 0.95694035, 0.98078525, 0.9951847, 1.0,
+];
+
+/// The way in which the coils of a bipolar stepper motor are driven.
+///
+/// | Mode         | Coils                                          | Steps  |
+/// |--------------|------------------------------------------------|--------|
+/// | Wave         | one coil at a time, the least torque           | 4      |
+/// | Full         | both coils at a time, the most torque          | 4      |
+/// | Half         | one and two coils in turn, twice the steps     | 8      |
+/// | Microstep(n) | sine and cosine of the current, the smoothest  | 4 * n  |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    Wave,
+    Full,
+    Half,
+    Microstep(u8),
+}
+
+impl StepMode {
+    /// Returns whether the mode can be driven. Microstepping divides a full
+    /// step in 2, 4, 8 or 16 microsteps.
+    pub fn is_valid(self) -> bool {
+        return match self {
+            StepMode::Microstep(n) => matches!(n, 2 | 4 | 8 | 16),
+            _ => true,
+        };
+    }
+
+    /// Returns the number of phase units that one step of this mode moves.
+    pub fn stride(self) -> i64 {
+        return match self {
+            StepMode::Wave | StepMode::Full => PHASES_PER_STEP,
+            StepMode::Half => PHASES_PER_STEP / 2,
+            StepMode::Microstep(n) => PHASES_PER_STEP / n.max(1) as i64,
+        };
+    }
+
+    /// Returns the number of steps of this mode in one revolution of a motor
+    /// with the specified number of full steps per revolution.
+    pub fn steps_per_revolution(self, full_steps: u32) -> u32 {
+        return full_steps * (PHASES_PER_STEP / self.stride()) as u32;
+    }
+
+    /// Returns the phase of the sequence of this mode that is nearest to the
+    /// specified phase, rounding backward. Wave steps are on the phases
+    /// where one coil is on, and full steps halfway between them, where both
+    /// coils are on.
+    pub fn align(self, phase: i64) -> i64 {
+        return match self {
+            StepMode::Full => {
+                let half = PHASES_PER_STEP / 2;
+                phase - (phase - half).rem_euclid(PHASES_PER_STEP)
+            },
+            _ => phase - phase.rem_euclid(self.stride()),
+        };
+    }
+
+    /// Returns whether the enables of the coils are modulated in this mode.
+    pub fn is_microstep(self) -> bool {
+        return matches!(self, StepMode::Microstep(_));
+    }
+}
+
+/// Returns the sine of the phase, where 4 full steps are one period.
+pub fn sine(phase: i64) -> f32 {
+    let n = PHASES_PER_STEP;
+    let r = phase.rem_euclid(n) as usize;
+    return match phase.rem_euclid(4 * n) / n {
+        0 => SINE[r],
+        1 => SINE[n as usize - r],
+        2 => -SINE[r],
+        _ => -SINE[n as usize - r],
+    };
+}
+
+/// Returns the current through coil A and coil B at the specified phase,
+/// from -1.0 to 1.0. Coil A follows the cosine and coil B the sine.
+pub fn coil_duties(phase: i64) -> (f32, f32) {
+    return (sine(phase + PHASES_PER_STEP), sine(phase));
+}
+
+/// Returns the direction of the current through coil A and coil B at the
+/// specified phase: 1 forward, -1 backward and 0 off.
+pub fn coil_currents(phase: i64) -> (i8, i8) {
+    let (a, b) = coil_duties(phase);
+    return (signum(a), signum(b));
+}
+
+/// Returns the state of the L293D inputs that drive a coil with the
+/// specified current. A coil without current has both inputs low.
+pub fn coil_state(current: i8) -> SlotState {
+    return match current {
+        c if c > 0 => SlotState::Run(Direction::Clockwise),
+        c if c < 0 => SlotState::Run(Direction::Counterclockwise),
+        _ => SlotState::Stopped(StopMode::Release),
+    };
+}
+
+/// Returns the time between two steps at the specified speed, or `None` if
+/// the speed is not above zero.
+pub fn step_interval(rpm: f32, steps_per_revolution: u32) -> Option<Duration> {
+    if !rpm.is_finite() || rpm <= 0.0 || steps_per_revolution == 0 {
+        return None;
+    }
+
+    return Some(Duration::from_secs_f64(60.0 / (rpm as f64 * steps_per_revolution as f64)));
+}
+
+/// Returns the sign of a current, where zero has no sign.
+fn signum(value: f32) -> i8 {
+    if value > 0.0 {
+        return 1;
+    }
+
+    if value < 0.0 {
+        return -1;
+    }
+
+    return 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_test() {
+        // wave steps turn one coil on at a time
+        let wave: Vec<(i8, i8)> = (0..4).map(|s| coil_currents(StepMode::Wave.align(0) + s * 16)).collect();
+        assert_eq!(vec![(1, 0), (0, 1), (-1, 0), (0, -1)], wave);
+
+        // full steps turn both coils on at a time
+        let full: Vec<(i8, i8)> = (0..4).map(|s| coil_currents(StepMode::Full.align(0) + s * 16)).collect();
+        assert_eq!(vec![(1, -1), (1, 1), (-1, 1), (-1, -1)], full);
+
+        // only one coil changes between two half steps
+        for h in -8..8 {
+            let (a0, b0) = coil_currents(h * 8);
+            let (a1, b1) = coil_currents(h * 8 + 8);
+            assert!((a0 == a1) != (b0 == b1));
+        }
+    }
+
+    #[test]
+    fn microstep_duties_test() {
+        // a quarter of the sine at 8 microsteps per step
+        let stride = StepMode::Microstep(8).stride();
+        for i in 0..=8 {
+            let angle = (i * stride) as f32 * core::f32::consts::PI / 32.0;
+            let (a, b) = coil_duties(i * stride);
+            assert!((a - angle.cos()).abs() < 1e-6);
+            assert!((b - angle.sin()).abs() < 1e-6);
+        }
+
+        // the other quarters mirror the first
+        for phase in -64..64 {
+            let angle = phase as f32 * core::f32::consts::PI / 32.0;
+            assert!((sine(phase) - angle.sin()).abs() < 1e-6);
+        }
+
+        assert!(StepMode::Microstep(16).is_valid());
+        assert!(!StepMode::Microstep(3).is_valid());
+        assert_eq!(3200, StepMode::Microstep(16).steps_per_revolution(200));
+    }
+
+    #[test]
+    fn align_test() {
+        assert_eq!(16, StepMode::Wave.align(20));
+        assert_eq!(-16, StepMode::Wave.align(-1));
+        assert_eq!(8, StepMode::Full.align(20));
+        assert_eq!(-8, StepMode::Full.align(-2));
+        assert_eq!(16, StepMode::Half.align(20));
+        assert_eq!(20, StepMode::Microstep(4).align(22));
+        assert_eq!(400, StepMode::Half.steps_per_revolution(200));
+        assert_eq!(200, StepMode::Wave.steps_per_revolution(200));
+    }
+
+    #[test]
+    fn step_interval_test() {
+        assert_eq!(Some(Duration::from_millis(5)), step_interval(60.0, 200));
+        assert_eq!(None, step_interval(0.0, 200));
+        assert_eq!(None, step_interval(f32::NAN, 200));
+        assert_eq!(SlotState::Stopped(StopMode::Release), coil_state(0));
+        assert_eq!(SlotState::Run(Direction::Counterclockwise), coil_state(-1));
+    }
+}
@@ -0,0 +1,745 @@
+use crate::sysfs::SysfsPwm;
+use motor_shield_core::encoder;
+use motor_shield_core::pins::PinError;
+use rppal::gpio::{InputPin as RppalInputPin, OutputPin as RppalPin, Gpio, Error as GpioError, Level, Trigger};
+use rppal::i2c::Error as I2cError;
+use embedded_hal::digital::{self, InputPin, OutputPin};
+use embedded_hal::i2c;
+use embedded_hal::pwm::{self, SetDutyCycle};
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub use motor_shield_core::pwm::set_pulse_width;
+
+/// A source of pins. The board, motors and servos claim their pins through a
+/// backend, so the same code can drive the pins of a Raspberry Pi, an
+/// in-memory recorder or the pins of any other embedded-hal implementation.
+pub trait GpioBackend {
+    /// The digital output pin type that is handed out by this backend.
+    type Output: OutputPin;
+    /// The pwm output pin type that is handed out by this backend.
+    type Pwm: SetDutyCycle;
+
+    /// Claims the pin with the specified number as a digital output pin.
+    fn output(&self, pin: u8) -> Result<Self::Output, BackendError>;
+
+    /// Claims the pin with the specified number as a pwm output pin which
+    /// runs with the specified period.
+    fn pwm(&self, pin: u8, period: Duration) -> Result<Self::Pwm, BackendError>;
+}
+
+/// A source of input pins. This is separate from `GpioBackend`, so a backend
+/// that only drives outputs does not have to hand out inputs.
+pub trait InputBackend {
+    /// The input pin type that is handed out by this backend.
+    type Input: EdgeInput;
+
+    /// Claims the pin with the specified number as an input pin.
+    fn input(&self, pin: u8) -> Result<Self::Input, BackendError>;
+}
+
+/// The callback of an edge on an input pin, which gets whether the pin is
+/// high after the edge.
+pub type EdgeCallback = Box<dyn FnMut(bool) + Send>;
+
+/// A digital input pin that can report its edges.
+pub trait EdgeInput {
+    /// Returns whether the pin is high.
+    fn is_high(&mut self) -> Result<bool, BackendError>;
+
+    /// Calls the callback on every edge of the pin, which may happen on
+    /// another thread. A pin without interrupts returns
+    /// `BackendError::Unsupported` and has to be polled instead.
+    fn on_edge(&mut self, callback: EdgeCallback) -> Result<(), BackendError>;
+}
+
+/// Returns the maximum duty cycle for a pwm signal with the specified period.
+/// The duty cycle is expressed in microseconds as long as the period fits.
+pub(crate) fn max_duty_for(period: Duration) -> u16 {
+    return period.as_micros().clamp(1, u16::MAX as u128) as u16;
+}
+
+/// Returns the pulse width for a duty cycle of a pwm signal.
+pub(crate) fn pulse_width_for(period: Duration, duty: u16, max: u16) -> Duration {
+    return Duration::from_nanos((period.as_nanos() * duty as u128 / max as u128) as u64);
+}
+
+/// The output of the pwm signal of a motor or servo. This is either a pin
+/// of the backend, or a channel of a hardware pwm chip.
+#[derive(Debug)]
+pub enum PwmOutput<P> {
+    Pin(P),
+    Channel(SysfsPwm),
+}
+
+impl<P: SetDutyCycle> pwm::ErrorType for PwmOutput<P> {
+    type Error = BackendError;
+}
+
+impl<P: SetDutyCycle> SetDutyCycle for PwmOutput<P> {
+    fn max_duty_cycle(&self) -> u16 {
+        return match self {
+            PwmOutput::Pin(p) => p.max_duty_cycle(),
+            PwmOutput::Channel(c) => c.max_duty_cycle(),
+        };
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), BackendError> {
+        return match self {
+            PwmOutput::Pin(p) => p.set_duty_cycle(duty).map_err(BackendError::pwm),
+            PwmOutput::Channel(c) => c.set_duty_cycle(duty),
+        };
+    }
+}
+
+/// The backend that uses rppal to drive the pins of a Raspberry Pi. Pwm is
+/// generated in software by rppal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RppalBackend;
+
+impl RppalBackend {
+    /// Claims a pin from the gpio peripheral as an rppal output pin.
+    fn claim(&self, pin: u8) -> Result<RppalPin, BackendError> {
+        let gpio = Gpio::new()?;
+        return Ok(gpio.get(pin)?.into_output());
+    }
+}
+
+impl InputBackend for RppalBackend {
+    type Input = RppalInput;
+
+    fn input(&self, pin: u8) -> Result<RppalInput, BackendError> {
+        let gpio = Gpio::new()?;
+        return Ok(RppalInput { pin: gpio.get(pin)?.into_input_pullup() });
+    }
+}
+
+impl GpioBackend for RppalBackend {
+    type Output = RppalOutput;
+    type Pwm = RppalPwm;
+
+    fn output(&self, pin: u8) -> Result<RppalOutput, BackendError> {
+        return Ok(RppalOutput { pin: self.claim(pin)? });
+    }
+
+    fn pwm(&self, pin: u8, period: Duration) -> Result<RppalPwm, BackendError> {
+        return Ok(RppalPwm { pin: self.claim(pin)?, period });
+    }
+}
+
+/// A digital output pin of the `RppalBackend`.
+#[derive(Debug)]
+pub struct RppalOutput {
+    pin: RppalPin,
+}
+
+impl digital::ErrorType for RppalOutput {
+    type Error = Infallible;
+}
+
+impl OutputPin for RppalOutput {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.pin.set_low();
+        return Ok(());
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.pin.set_high();
+        return Ok(());
+    }
+}
+
+/// An input pin of the `RppalBackend`. The pin is pulled up, since most
+/// motor encoders have open collector outputs. Edges are reported by the
+/// asynchronous interrupts of rppal.
+#[derive(Debug)]
+pub struct RppalInput {
+    pin: RppalInputPin,
+}
+
+impl EdgeInput for RppalInput {
+    fn is_high(&mut self) -> Result<bool, BackendError> {
+        return Ok(self.pin.is_high());
+    }
+
+    fn on_edge(&mut self, callback: EdgeCallback) -> Result<(), BackendError> {
+        let mut callback = callback;
+        self.pin.set_async_interrupt(Trigger::Both, move |level| callback(level == Level::High))?;
+        return Ok(());
+    }
+}
+
+/// A pwm output pin of the `RppalBackend`. A duty cycle of zero or the
+/// maximum stops the software pwm and holds the pin low or high.
+#[derive(Debug)]
+pub struct RppalPwm {
+    pin: RppalPin,
+    period: Duration,
+}
+
+impl pwm::ErrorType for RppalPwm {
+    type Error = BackendError;
+}
+
+impl SetDutyCycle for RppalPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        return max_duty_for(self.period);
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), BackendError> {
+        let max = self.max_duty_cycle();
+        if duty == 0 || duty >= max {
+            self.pin.clear_pwm()?;
+            if duty == 0 {
+                self.pin.set_low();
+            } else {
+                self.pin.set_high();
+            }
+            return Ok(());
+        }
+
+        self.pin.set_pwm(self.period, pulse_width_for(self.period, duty, max))?;
+        return Ok(());
+    }
+}
+
+/// A backend for pins of another embedded-hal implementation. It can not
+/// claim pins by number, so the pins have to be handed to the board, motors
+/// and servos directly.
+pub struct HalPins<O, P> {
+    pins: PhantomData<fn() -> (O, P)>,
+}
+
+impl<O, P> HalPins<O, P> {
+    /// Creates a new backend for the pin types `O` and `P`.
+    pub fn new() -> HalPins<O, P> {
+        return HalPins { pins: PhantomData };
+    }
+}
+
+impl<O, P> Default for HalPins<O, P> {
+    fn default() -> HalPins<O, P> {
+        return HalPins::new();
+    }
+}
+
+impl<O, P> Clone for HalPins<O, P> {
+    fn clone(&self) -> HalPins<O, P> {
+        return HalPins::new();
+    }
+}
+
+impl<O: OutputPin, P: SetDutyCycle> GpioBackend for HalPins<O, P> {
+    type Output = O;
+    type Pwm = P;
+
+    fn output(&self, _pin: u8) -> Result<O, BackendError> {
+        return Err(BackendError::Unsupported);
+    }
+
+    fn pwm(&self, _pin: u8, _period: Duration) -> Result<P, BackendError> {
+        return Err(BackendError::Unsupported);
+    }
+}
+
+/// An input pin of another embedded-hal implementation. Embedded-hal has no
+/// interrupts, so the pin does not report its edges and has to be polled.
+#[derive(Debug)]
+pub struct HalInput<P> {
+    pin: P,
+}
+
+impl<P: InputPin> HalInput<P> {
+    /// Wraps an embedded-hal input pin.
+    pub fn new(pin: P) -> HalInput<P> {
+        return HalInput { pin };
+    }
+
+    /// Returns the wrapped pin.
+    pub fn release(self) -> P {
+        return self.pin;
+    }
+}
+
+impl<P: InputPin> EdgeInput for HalInput<P> {
+    fn is_high(&mut self) -> Result<bool, BackendError> {
+        return self.pin.is_high().map_err(BackendError::digital);
+    }
+
+    fn on_edge(&mut self, _callback: EdgeCallback) -> Result<(), BackendError> {
+        return Err(BackendError::Unsupported);
+    }
+}
+
+/// A backend that keeps its pins in memory. Every action on a pin is
+/// recorded with a timestamp, so tests can assert on the exact pin traffic.
+/// Clones of the backend share the same recording.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    /// The events of all pins that were claimed from this backend.
+    events: Arc<Mutex<Vec<PinEvent>>>,
+    /// The levels and edge callbacks of the input pins.
+    inputs: Arc<Mutex<HashMap<u8, MemoryLine>>>,
+}
+
+impl MemoryBackend {
+    /// Creates a new memory backend without any recorded events.
+    pub fn new() -> MemoryBackend {
+        return MemoryBackend::default();
+    }
+
+    /// Returns all recorded events in the order they happened.
+    pub fn events(&self) -> Vec<PinEvent> {
+        return self.events.lock().unwrap().clone();
+    }
+
+    /// Returns the recorded actions of a single pin in the order they
+    /// happened.
+    pub fn actions(&self, pin: u8) -> Vec<PinAction> {
+        return self.events()
+            .iter()
+            .filter(|e| e.pin == pin)
+            .map(|e| e.action)
+            .collect();
+    }
+
+    /// Removes all recorded events.
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    /// Sets the level of an input pin. When the level changes, the edge
+    /// callbacks of the pin are called right away, like an interrupt. The
+    /// input pins are not locked while the callbacks run, so a callback can
+    /// read or set inputs itself.
+    pub fn set_input(&self, pin: u8, high: bool) {
+        let mut callbacks = {
+            let mut inputs = self.inputs.lock().unwrap();
+            let line = inputs.entry(pin).or_default();
+            if line.high == high {
+                return;
+            }
+
+            line.high = high;
+            std::mem::take(&mut line.callbacks)
+        };
+
+        for callback in callbacks.iter_mut() {
+            callback(high);
+        }
+
+        // callbacks that were added while the others ran are kept after them
+        let mut inputs = self.inputs.lock().unwrap();
+        let line = inputs.entry(pin).or_default();
+        callbacks.append(&mut line.callbacks);
+        line.callbacks = callbacks;
+    }
+
+    /// Returns the level of an input pin. Input pins start low.
+    pub fn input_level(&self, pin: u8) -> bool {
+        return self.inputs.lock().unwrap().get(&pin).map(|l| l.high).unwrap_or(false);
+    }
+
+    /// Feeds the edges of a quadrature encoder that turns the specified
+    /// number of counts into the A and B input pins, starting from their
+    /// current levels. A positive number turns forward, where A leads B.
+    pub fn feed_quadrature(&self, a: u8, b: u8, counts: i64) {
+        let current = (self.input_level(a), self.input_level(b));
+        let mut count = (0..4).find(|c| encoder::levels(*c) == current).unwrap_or(0);
+        for _ in 0..counts.abs() {
+            count += counts.signum();
+            let (la, lb) = encoder::levels(count);
+            self.set_input(a, la);
+            self.set_input(b, lb);
+        }
+    }
+}
+
+impl InputBackend for MemoryBackend {
+    type Input = MemoryInput;
+
+    fn input(&self, pin: u8) -> Result<MemoryInput, BackendError> {
+        self.inputs.lock().unwrap().entry(pin).or_default();
+        return Ok(MemoryInput {
+            pin,
+            inputs: self.inputs.clone(),
+        });
+    }
+}
+
+impl GpioBackend for MemoryBackend {
+    type Output = MemoryPin;
+    type Pwm = MemoryPwm;
+
+    fn output(&self, pin: u8) -> Result<MemoryPin, BackendError> {
+        return Ok(MemoryPin {
+            pin,
+            high: false,
+            events: self.events.clone(),
+        });
+    }
+
+    fn pwm(&self, pin: u8, period: Duration) -> Result<MemoryPwm, BackendError> {
+        return Ok(MemoryPwm {
+            pin: self.output(pin)?,
+            period,
+        });
+    }
+}
+
+/// A digital output pin that is claimed from a `MemoryBackend`.
+#[derive(Debug)]
+pub struct MemoryPin {
+    /// The number of the pin.
+    pin: u8,
+    /// Whether the pin was last set high.
+    high: bool,
+    /// The recording that is shared with the backend.
+    events: Arc<Mutex<Vec<PinEvent>>>,
+}
+
+impl MemoryPin {
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        return self.pin;
+    }
+
+    /// Returns whether the pin was last set high.
+    pub fn is_set_high(&self) -> bool {
+        return self.high;
+    }
+
+    /// Records an action on this pin.
+    fn record(&self, action: PinAction) {
+        self.events.lock().unwrap().push(PinEvent {
+            pin: self.pin,
+            action,
+            at: Instant::now(),
+        });
+    }
+}
+
+impl digital::ErrorType for MemoryPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for MemoryPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.high = false;
+        self.record(PinAction::Low);
+        return Ok(());
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.high = true;
+        self.record(PinAction::High);
+        return Ok(());
+    }
+}
+
+/// The level and the edge callbacks of an input pin of the `MemoryBackend`.
+#[derive(Default)]
+struct MemoryLine {
+    /// Whether the pin is high.
+    high: bool,
+    /// The callbacks that are called on every edge.
+    callbacks: Vec<EdgeCallback>,
+}
+
+impl fmt::Debug for MemoryLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_struct("MemoryLine")
+            .field("high", &self.high)
+            .field("callbacks", &self.callbacks.len())
+            .finish();
+    }
+}
+
+/// An input pin that is claimed from a `MemoryBackend`. Its level is set with
+/// `MemoryBackend::set_input`.
+#[derive(Debug, Clone)]
+pub struct MemoryInput {
+    /// The number of the pin.
+    pin: u8,
+    /// The input pins that are shared with the backend.
+    inputs: Arc<Mutex<HashMap<u8, MemoryLine>>>,
+}
+
+impl MemoryInput {
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        return self.pin;
+    }
+}
+
+impl EdgeInput for MemoryInput {
+    fn is_high(&mut self) -> Result<bool, BackendError> {
+        return Ok(self.inputs.lock().unwrap().get(&self.pin).map(|l| l.high).unwrap_or(false));
+    }
+
+    fn on_edge(&mut self, callback: EdgeCallback) -> Result<(), BackendError> {
+        self.inputs.lock().unwrap().entry(self.pin).or_default().callbacks.push(callback);
+        return Ok(());
+    }
+}
+
+/// A pwm output pin that is claimed from a `MemoryBackend`. Like the rppal
+/// pin, a duty cycle of zero or the maximum is recorded as the pin being set
+/// low or high.
+#[derive(Debug)]
+pub struct MemoryPwm {
+    /// The underlying pin that records the actions.
+    pin: MemoryPin,
+    /// The period of the pwm signal.
+    period: Duration,
+}
+
+impl MemoryPwm {
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        return self.pin.pin();
+    }
+
+    /// Returns the period of the pwm signal of this pin.
+    pub fn period(&self) -> Duration {
+        return self.period;
+    }
+}
+
+impl pwm::ErrorType for MemoryPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for MemoryPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        return max_duty_for(self.period);
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        let max = self.max_duty_cycle();
+        if duty == 0 {
+            return self.pin.set_low();
+        }
+
+        if duty >= max {
+            return self.pin.set_high();
+        }
+
+        self.pin.high = false;
+        self.pin.record(PinAction::Pwm {
+            period: self.period,
+            pulse_width: pulse_width_for(self.period, duty, max),
+        });
+        return Ok(());
+    }
+}
+
+/// A single recorded action on a pin of the `MemoryBackend`.
+#[derive(Debug, Clone, Copy)]
+pub struct PinEvent {
+    /// The number of the pin.
+    pub pin: u8,
+    /// What happened to the pin.
+    pub action: PinAction,
+    /// When it happened.
+    pub at: Instant,
+}
+
+/// The actions that can be recorded on a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinAction {
+    High,
+    Low,
+    Pwm { period: Duration, pulse_width: Duration },
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    Gpio(GpioError),
+    I2c(I2cError),
+    Io(std::io::Error),
+    Digital(digital::ErrorKind),
+    Pwm(pwm::ErrorKind),
+    Bus(i2c::ErrorKind),
+    InvalidPin(PinError),
+    Unsupported,
+}
+
+impl BackendError {
+    /// Converts the error of a digital pin into a backend error.
+    pub fn digital<E: digital::Error>(e: E) -> BackendError {
+        return BackendError::Digital(e.kind());
+    }
+
+    /// Converts the error of a pwm pin into a backend error.
+    pub fn pwm<E: pwm::Error>(e: E) -> BackendError {
+        return BackendError::Pwm(e.kind());
+    }
+
+    /// Converts the error of an I2C bus into a backend error.
+    pub fn i2c<E: i2c::Error>(e: E) -> BackendError {
+        return BackendError::Bus(e.kind());
+    }
+}
+
+impl From<GpioError> for BackendError {
+    fn from(e: GpioError) -> BackendError {
+        return BackendError::Gpio(e);
+    }
+}
+
+impl From<I2cError> for BackendError {
+    fn from(e: I2cError) -> BackendError {
+        return BackendError::I2c(e);
+    }
+}
+
+impl From<PinError> for BackendError {
+    fn from(e: PinError) -> BackendError {
+        return BackendError::InvalidPin(e);
+    }
+}
+
+impl From<std::io::Error> for BackendError {
+    fn from(e: std::io::Error) -> BackendError {
+        return BackendError::Io(e);
+    }
+}
+
+impl digital::Error for BackendError {
+    fn kind(&self) -> digital::ErrorKind {
+        return match self {
+            BackendError::Digital(k) => *k,
+            _ => digital::ErrorKind::Other,
+        };
+    }
+}
+
+impl pwm::Error for BackendError {
+    fn kind(&self) -> pwm::ErrorKind {
+        return match self {
+            BackendError::Pwm(k) => *k,
+            _ => pwm::ErrorKind::Other,
+        };
+    }
+}
+
+impl i2c::Error for BackendError {
+    fn kind(&self) -> i2c::ErrorKind {
+        return match self {
+            BackendError::Bus(k) => *k,
+            _ => i2c::ErrorKind::Other,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_backend_records_pin_actions() {
+        let backend = MemoryBackend::new();
+        let mut pin = backend.output(5).unwrap();
+        pin.set_high().unwrap();
+        pin.set_low().unwrap();
+
+        let mut pwm = backend.pwm(6, Duration::from_millis(20)).unwrap();
+        set_pulse_width(&mut pwm, Duration::from_millis(20), Duration::from_millis(5)).unwrap();
+        pwm.set_duty_cycle_fully_on().unwrap();
+
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(5));
+        let expected = vec![
+            PinAction::Pwm { period: Duration::from_millis(20), pulse_width: Duration::from_millis(5) },
+            PinAction::High,
+        ];
+        assert_eq!(expected, backend.actions(6));
+        assert!(!pin.is_set_high());
+    }
+
+    #[test]
+    fn memory_backend_clones_share_events() {
+        let backend = MemoryBackend::new();
+        let mut a = backend.clone().output(1).unwrap();
+        let mut b = backend.output(2).unwrap();
+        a.set_high().unwrap();
+        b.set_high().unwrap();
+
+        let events = backend.events();
+        assert_eq!(2, events.len());
+        assert_eq!(1, events[0].pin);
+        assert_eq!(2, events[1].pin);
+        assert!(events[0].at <= events[1].at);
+
+        backend.clear();
+        assert!(backend.events().is_empty());
+    }
+
+    #[test]
+    fn pulse_width_is_a_ratio_of_the_cycle() {
+        let backend = MemoryBackend::new();
+        let mut pwm = backend.pwm(1, Duration::from_millis(10)).unwrap();
+        set_pulse_width(&mut pwm, Duration::from_millis(20), Duration::from_millis(5)).unwrap();
+        set_pulse_width(&mut pwm, Duration::from_millis(20), Duration::from_millis(30)).unwrap();
+        set_pulse_width(&mut pwm, Duration::ZERO, Duration::from_millis(5)).unwrap();
+
+        let expected = vec![
+            PinAction::Pwm { period: Duration::from_millis(10), pulse_width: Duration::from_micros(2500) },
+            PinAction::High,
+            PinAction::Low,
+        ];
+        assert_eq!(expected, backend.actions(1));
+    }
+
+    #[test]
+    fn memory_inputs_call_edge_callbacks() {
+        let backend = MemoryBackend::new();
+        let mut input = backend.input(4).unwrap();
+        let edges = Arc::new(Mutex::new(Vec::new()));
+        let seen = edges.clone();
+        input.on_edge(Box::new(move |high| seen.lock().unwrap().push(high))).unwrap();
+
+        backend.set_input(4, true);
+        backend.set_input(4, true);
+        backend.set_input(4, false);
+        assert_eq!(vec![true, false], *edges.lock().unwrap());
+        assert!(!input.is_high().unwrap());
+
+        backend.feed_quadrature(4, 5, 3);
+        assert_eq!((false, true), (backend.input_level(4), backend.input_level(5)));
+    }
+
+    #[test]
+    fn edge_callbacks_can_use_the_backend() {
+        let backend = MemoryBackend::new();
+        let mut input = backend.input(4).unwrap();
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let seen = levels.clone();
+        let inner = backend.clone();
+        input.on_edge(Box::new(move |high| {
+            seen.lock().unwrap().push(inner.input_level(4));
+            inner.set_input(5, high);
+        })).unwrap();
+
+        backend.set_input(4, true);
+        assert_eq!(vec![true], *levels.lock().unwrap());
+        assert!(backend.input_level(5));
+
+        // the callback is kept for the next edge
+        backend.set_input(4, false);
+        assert_eq!(vec![true, false], *levels.lock().unwrap());
+        assert!(!backend.input_level(5));
+    }
+
+    #[test]
+    fn hal_pins_can_not_claim_by_number() {
+        let backend: HalPins<MemoryPin, MemoryPwm> = HalPins::new();
+        assert!(matches!(backend.output(1), Err(BackendError::Unsupported)));
+        assert!(matches!(backend.pwm(1, Duration::from_millis(20)), Err(BackendError::Unsupported)));
+    }
+}
@@ -1,35 +1,117 @@
-use crate::motor::{Motor, MotorError, Direction, MotorConfig};
-use rppal::gpio::{OutputPin, Gpio, Error as GpioError};
+use crate::motor::{Motor, MotorError, Direction, MotorConfig, SlotState, StopMode};
+use crate::backend::{GpioBackend, BackendError, RppalBackend};
+use crate::encoder::Encoder;
+use crate::servo::{Servo, ServoConfig, ServoError};
+use crate::wiring::{ArduinoPin, ShieldWiring};
+use motor_shield_core::directions;
+use motor_shield_core::pins::{self, PinError};
+use motor_shield_core::shift_register::{ShiftRegister, ShiftRegisterError};
 use core::time::Duration;
-use std::matches;
 
 /// # The AMSBoard structure
-pub struct AMSBoard {
-    /// the serial pin
-    pin_ser: Option<OutputPin>,
-    /// the clock pin
-    pin_clk: Option<OutputPin>,
-    /// the latch pin
-    pin_lat: Option<OutputPin>,
+pub struct AMSBoard<B: GpioBackend = RppalBackend> {
+    /// the backend that the shift register pins are claimed from
+    backend: B,
+    /// the shift register that holds the directions
+    register: Option<ShiftRegister<B::Output>>,
+    /// the bytes of the registers that are daisy-chained after the shield
+    chained: Vec<u8>,
+    /// whether a byte has been latched onto the shift register
+    latched: bool,
+    /// whether the outputs of the shift register are disabled by the user
+    outputs_disabled: bool,
     /// the list that holds the motors
-    motors: [Option<Motor>; 4],
-    /// the board type, currently does nothing
-    _type: BoardType,
+    motors: [Option<Motor<B>>; 4],
+    /// whether the motor of every slot is wired the other way around
+    inverted: [bool; 4],
+    /// the encoders that are attached to the motor slots
+    encoders: [Option<Encoder>; 4],
+    /// the list that holds the servos
+    servos: [Option<Servo<B>>; 2],
+    /// the numbering of the pins that are passed to the board
+    board_type: BoardType,
     /// the byte that holds the directions of the motors
     directions: u8,
 }
   
 impl AMSBoard {
-    /// Creates a new AMSBoard. The `BoardType` parameter is required but 
-    /// currently serves no functionality.
+    /// Creates a new AMSBoard that drives its pins using rppal. The
+    /// `BoardType` decides how the pin numbers passed to the board are
+    /// interpreted.
     pub fn new(t: BoardType) -> AMSBoard {
+        return AMSBoard::with_backend(t, RppalBackend);
+    }
+}
+
+impl<B: GpioBackend + Clone> AMSBoard<B> {
+    /// Creates a fully configured board from the wiring of the Arduino
+    /// header. The shift register pins D4, D8 and D12 are required. The
+    /// output enable D7, the motor enables and the servo pins are optional,
+    /// every motor and servo that is wired is set in its slot. Servos start
+    /// in the default configuration.
+    pub fn from_wiring(t: BoardType, backend: B, wiring: &ShieldWiring) -> Result<AMSBoard<B>, BoardError> {
+        println!("creating board from wiring");
+        let mut board = AMSBoard::with_backend(t, backend);
+        let ser = board.wired_output(wiring, ArduinoPin::DIR_SER)?;
+        let clk = board.wired_output(wiring, ArduinoPin::DIR_CLK)?;
+        let lat = board.wired_output(wiring, ArduinoPin::DIR_LATCH)?;
+        board.set_shift_register_outputs(ser, clk, lat);
+
+        if let Some(oe) = wiring.get(ArduinoPin::DIR_EN) {
+            board.set_output_enable_pin(oe)?;
+        }
+
+        for slot in 1..=4 {
+            board.inverted[slot - 1] = wiring.is_inverted(slot);
+            let enable = ArduinoPin::motor_enable(slot).and_then(|p| wiring.get(p));
+            if let Some(pin) = enable {
+                let mut motor = Motor::with_backend(board.backend.clone());
+                motor.set_board_type(t);
+                motor.set_pin(pin).map_err(BoardError::Backend)?;
+                board.set_motor(motor, slot).map_err(BoardError::MotorError)?;
+            }
+        }
+
+        for slot in 1..=2 {
+            let pulse = ArduinoPin::servo(slot).and_then(|p| wiring.get(p));
+            if let Some(pin) = pulse {
+                let servo = Servo::new_servo_with_type(&board.backend, t, ServoConfig::new_default(), pin)
+                    .map_err(BoardError::ServoError)?;
+                board.set_servo(servo, slot).map_err(BoardError::ServoError)?;
+            }
+        }
+
+        return Ok(board);
+    }
+
+    /// Claims the output that is wired to the specified Arduino pin.
+    fn wired_output(&self, wiring: &ShieldWiring, arduino: ArduinoPin) -> Result<B::Output, BoardError> {
+        let pin = match wiring.get(arduino) {
+            Some(p) => p,
+            None => return Err(BoardError::WiringPinNotSet(arduino)),
+        };
+
+        let bcm = self.board_type.to_bcm(pin).map_err(|e| BoardError::Backend(e.into()))?;
+        return self.backend.output(bcm).map_err(BoardError::Backend);
+    }
+}
+
+impl<B: GpioBackend> AMSBoard<B> {
+    /// Creates a new AMSBoard that claims its shift register pins from the
+    /// specified backend.
+    pub fn with_backend(t: BoardType, backend: B) -> AMSBoard<B> {
         println!("creating new board");
         return AMSBoard {
-            pin_ser: None,
-            pin_clk: None,
-            pin_lat: None,
+            backend,
+            register: None,
+            chained: Vec::new(),
+            latched: false,
+            outputs_disabled: false,
             motors: [None, None, None, None],
-            _type: t,
+            inverted: [false; 4],
+            encoders: [None, None, None, None],
+            servos: [None, None],
+            board_type: t,
             directions: 0,
         }
     }
@@ -43,47 +125,18 @@ impl AMSBoard {
     /// motors direction will make all other motors stall.
     fn calculate_directions(&self) -> u8 {
         println!("calculating directions of motors");
-        let m1_dir: u8 = match &self.motors[0] {
-            Some(m) => match m.get_direction(){
-                Direction::Clockwise => 4,
-                Direction::Counterclockwise => 8
-            },
-            _ => 0
-        };
-        println!("motor 1: {:#010b}", m1_dir);
-
-        let m2_dir: u8 = match &self.motors[1] {
-            Some(m) => match m.get_direction(){
-                Direction::Clockwise => 2,
-                Direction::Counterclockwise => 16 
-            },
-            _ => 0
-        };
-
-        println!("motor 2: {:#010b}", m2_dir);
-        
-        let m3_dir: u8 = match &self.motors[2] {
-            Some(m) => match m.get_direction(){
-                Direction::Clockwise => 1,
-                Direction::Counterclockwise => 64 
-            },
-            _ => 0
-        };
-        
-        println!("motor 3: {:#010b}", m3_dir);
-
-        let m4_dir: u8 = match &self.motors[3] {
-            Some(m) => match m.get_direction(){
-                Direction::Clockwise => 32,
-                Direction::Counterclockwise => 128 
-            },
-            _ => 0
-        };
-        println!("motor 4: {:#010b}", m4_dir);
+        let mut slots: [Option<SlotState>; 4] = [None; 4];
+        for (i, motor) in self.motors.iter().enumerate() {
+            slots[i] = motor.as_ref().map(|m| match self.inverted[i] {
+                true => m.get_state().inverted(),
+                false => m.get_state(),
+            });
+        }
 
-        println!("total direction: {:#010b}", m1_dir | m2_dir | m3_dir | m4_dir);
+        let directions = directions::calculate_states(slots);
+        println!("total direction: {:#010b}", directions);
 
-        return m1_dir | m2_dir | m3_dir | m4_dir; 
+        return directions;
     }
 
     /// An internal function to update the directions of this board. It
@@ -94,95 +147,82 @@ impl AMSBoard {
         self.directions = self.calculate_directions();
     }
 
-    /// Checks if the register pins are valid. If any of them is `None`, the
-    /// function will return false.
-    fn register_pins_are_valid(&self) -> bool {
-        println!("checking if pins are valid");
-        if self.pin_clk.is_none() {
-            return false;
-        }
-        
-        if self.pin_ser.is_none() {
-            return false;
-        }
-
-        if self.pin_lat.is_none() {
-            return false;
-        }
-
-        println!("pins are valid!");
-
-        return true;
-    }
-
     /// This function updates the shift register. It does this by looking at 
     /// the directions property of the AMSBoard struct, and iterating over each
-    /// bit from MSB to LSB, pushing it onto the register. The latch is then
-    /// opened and the byte is stored into the memory.
+    /// bit from MSB to LSB, pushing it onto the register. The bytes of any
+    /// daisy-chained registers are pushed after it. The latch is then opened
+    /// and the bytes are stored into the memory.
     fn update_shift_register(&mut self) -> Result<(), BoardError> {
-        if !self.register_pins_are_valid() {
-            return Err(BoardError::RegisterPinNotSet);
-        }
-
-        let latch = self.pin_lat.as_mut().unwrap();
-        let serial = self.pin_ser.as_mut().unwrap();
-        let clock = self.pin_clk.as_mut().unwrap();
-
-        println!("\tpushing to shift register. latch={:?}, serial={:?} clock={:?}", latch.pin(), serial.pin(), clock.pin());
-
-        latch.set_low(); // set the latch low before pushing
-        println!("\tset latch low");
-        let mut b: u16 = 128; // initiate the MSB as the first bit to be pushed
-        while b != 0 { // continue as long as we still have to push a bit
-            println!("setting bit position {:#010b}", b);
-            println!("\tset clock low");
-            clock.set_low(); // set the clock low, we need a rising edge to push
-            let c: u16 = b & (self.directions as u16); // bitwise AND with the bit to push
-            if c == b { // if the directions had a 1 on that bit, it is still 1
-                println!("\tset serial high");
-                serial.set_high(); // this means that this push should be high
-            } else { // otherwise it was a 0
-                println!("\tset serial low");
-                serial.set_low(); // so we push a low
-            }
-            println!("\tset clock high");
-            clock.set_high(); // set the clock high, a rising edge will push the data
-            b = b >> 1; // shift the bit to push to the right
-        }        
+        let register = match self.register.as_mut() {
+            Some(r) => r,
+            None => return Err(BoardError::RegisterPinNotSet),
+        };
+
+        let mut bytes = Vec::with_capacity(1 + self.chained.len());
+        bytes.push(self.directions);
+        bytes.extend_from_slice(&self.chained);
 
+        println!("\tpushing to shift register");
+        register.push(&bytes).map_err(BoardError::pin)?;
         println!("\tdirections are set to {:#010b}", self.directions);
-        println!("\tset latch high");
-        latch.set_high(); // once all bits are pushed, store in memory by opening the latch
 
-        println!("\tlatch.is_high={:?}\n\tclock.is_high={:?}\n\tserial.is_high={:?}", latch.is_set_high(), clock.is_set_high(), serial.is_set_high());
+        // the outputs are held disabled until the first byte is latched, so
+        // the random state of the register after power up never reaches the
+        // L293D inputs
+        if !self.latched {
+            self.latched = true;
+            if register.has_output_enable() && !self.outputs_disabled {
+                println!("\tenabling outputs after first latch");
+                register.enable_outputs().map_err(BoardError::register)?;
+            }
+        }
 
         return Ok(());
     }
 
+    /// Pushes the directions onto the shift register. Motors can still be
+    /// driven without the register pins set, so only that error is ignored.
+    fn push_directions(&mut self) -> Result<(), BoardError> {
+        return match self.update_shift_register() {
+            Err(BoardError::RegisterPinNotSet) => {
+                println!("shift register pins are not set, directions are not pushed");
+                Ok(())
+            },
+            r => r,
+        };
+    }
+
     /// Starts a motor using a configuration. Used internally to unify the 
     /// process of starting a motor over all public accessors.
     fn start_motor(&mut self, n: usize, cfg: MotorConfig) -> Result<(), BoardError> {
-        if n < 1 || n > 4 {
+        if !(1..=4).contains(&n) {
             return Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds));
         }
 
         if self.motors[n - 1].is_none() {
             return Err(BoardError::MotorError(MotorError::MotorNotFound));
         }
+
+        // a stopped motor gets its direction back on the inputs
+        let motor: &mut Motor<B> = self.motors[n - 1].as_mut().unwrap();
+        if motor.get_stop_mode().is_some() {
+            motor.set_stop_mode(None);
+            self.update_directions();
+        }
         
-        self.update_shift_register(); // first update the shift register
-        let motor: &mut Motor = self.motors[n - 1].as_mut().unwrap(); // retrieve the motor
+        self.push_directions()?; // first update the shift register
+        let motor: &mut Motor<B> = self.motors[n - 1].as_mut().unwrap(); // retrieve the motor
         
         println!("starting motor");
-        motor.start(cfg); // start the motor with the configuration
+        motor.start(cfg).map_err(BoardError::MotorError)?; // start the motor with the configuration
 
         return Ok(());
     }
     
     /// Returns a mutable reference to the motor that is requested. If there
     /// is no motor found, a MotorError is returned.
-    pub fn get_motor(&mut self, n: usize) -> Result<&mut Motor, MotorError> {
-        if n < 1 || n > 4 {
+    pub fn get_motor(&mut self, n: usize) -> Result<&mut Motor<B>, MotorError> {
+        if !(1..=4).contains(&n) {
             return Err(MotorError::MotorIndexOutOfBounds);
         }
 
@@ -195,47 +235,258 @@ impl AMSBoard {
 
     /// Sets a motor slot of the board. Use the M slots on the board to 
     /// indicate motors, since the directions are tied to these slots.
-    pub fn set_motor(&mut self, m: Motor, n: usize) -> Result<bool, MotorError> {
-        if n < 1 || n > 4 {
+    pub fn set_motor(&mut self, m: Motor<B>, n: usize) -> Result<bool, MotorError> {
+        if !(1..=4).contains(&n) {
             return Err(MotorError::MotorIndexOutOfBounds);
         }
 
         println!("set motor number {:?}", n);
+        let mut m = m;
+        m.set_board_type(self.board_type);
         self.motors[n - 1] = Some(m);
         self.update_directions();
 
         return Ok(true);
     }
 
+    /// Marks the motor in the specified slot as wired the other way around.
+    /// The directions of an inverted slot are swapped when the direction byte
+    /// is calculated, so `Direction::Clockwise` spins the motor clockwise no
+    /// matter how its leads are connected. The flag belongs to the slot, so
+    /// it is kept when another motor is set in the slot.
+    pub fn set_slot_inverted(&mut self, n: usize, inverted: bool) -> Result<(), MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        println!("set inversion of slot {:?} to {:?}", n, inverted);
+        self.inverted[n - 1] = inverted;
+        self.update_directions();
+        self.push_directions().map_err(BoardError::into_motor_error)?;
+
+        return Ok(());
+    }
+
+    /// Returns whether the motor in the specified slot is wired the other way
+    /// around.
+    pub fn is_slot_inverted(&self, n: usize) -> Result<bool, MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        return Ok(self.inverted[n - 1]);
+    }
+
+    /// Attaches an encoder to the specified motor slot, replacing the
+    /// encoder that was attached before. The encoder belongs to the slot, so
+    /// it is kept when another motor is set in the slot.
+    pub fn attach_encoder(&mut self, n: usize, e: Encoder) -> Result<(), MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        println!("attach encoder to slot {:?}", n);
+        self.encoders[n - 1] = Some(e);
+        return Ok(());
+    }
+
+    /// Removes the encoder from the specified motor slot and returns it.
+    pub fn detach_encoder(&mut self, n: usize) -> Result<Option<Encoder>, MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        println!("detach encoder from slot {:?}", n);
+        return Ok(self.encoders[n - 1].take());
+    }
+
+    /// Returns a mutable reference to the encoder of the specified motor
+    /// slot.
+    pub fn get_encoder(&mut self, n: usize) -> Result<&mut Encoder, MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        match &mut self.encoders[n - 1] {
+            Some(e) => return Ok(e),
+            None => return Err(MotorError::EncoderNotFound),
+        }
+    }
+
+    /// Returns a mutable reference to the servo that is requested. The slots
+    /// are numbered 1 and 2 like the servo headers on the motor shield.
+    pub fn get_servo(&mut self, n: usize) -> Result<&mut Servo<B>, ServoError> {
+        if !(1..=2).contains(&n) {
+            return Err(ServoError::ServoIndexOutOfBounds);
+        }
+
+        match &mut self.servos[n - 1] {
+            Some(s) => return Ok(s),
+            None => return Err(ServoError::ServoNotFound),
+        }
+    }
+
+    /// Sets a servo slot of the board.
+    pub fn set_servo(&mut self, s: Servo<B>, n: usize) -> Result<bool, ServoError> {
+        if !(1..=2).contains(&n) {
+            return Err(ServoError::ServoIndexOutOfBounds);
+        }
+
+        println!("set servo number {:?}", n);
+        self.servos[n - 1] = Some(s);
+
+        return Ok(true);
+    }
+
+    /// Returns the numbering of the pins that are passed to this board.
+    pub fn get_board_type(&self) -> BoardType {
+        return self.board_type;
+    }
+
     /// Set the shift register pins of the board. Without the shift register
     /// there is no addressing motors since the shift register is reset on 
     /// every restart, thus all motors will stall.
     pub fn set_shift_register_pins(&mut self, ser: u8, clk: u8, lat: u8) {
-        let gpio_res: Result<Gpio, GpioError> = Gpio::new(); 
-        let gpio = match gpio_res {
-            Ok(g) => g,
+        println!("setting shift register pins latch={:?} clock={:?} serial={:?}", lat, clk, ser);
+        let mut bcm = [ser, clk, lat];
+        for pin in bcm.iter_mut() {
+            *pin = match self.board_type.to_bcm(*pin) {
+                Ok(p) => p,
+                Err(e) => panic!("{:?}", e),
+            };
+        }
+        let [ser, clk, lat] = bcm;
+
+        let clock = match self.backend.output(clk) {
+            Ok(p) => p,
             Err(e) => panic!("{:?}", e),
         };
 
-        let latch_pin = gpio.get(lat);
-        let serial_pin = gpio.get(ser);
-        let clock_pin = gpio.get(clk); 
-        
-        println!("setting shift register pins latch={:?} clock={:?} serial={:?}", lat, clk, ser);
-        match clock_pin {
-            Ok(p) => self.pin_clk = Some(p.into_output()),
+        let latch = match self.backend.output(lat) {
+            Ok(p) => p,
             Err(e) => panic!("{:?}", e),
         };
 
-        match latch_pin {
-            Ok(p) => self.pin_lat = Some(p.into_output()),
+        let serial = match self.backend.output(ser) {
+            Ok(p) => p,
             Err(e) => panic!("{:?}", e),
         };
 
-        match serial_pin {
-            Ok(p) => self.pin_ser = Some(p.into_output()),
-            Err(e) => panic!("{:?}", e),
+        self.register = Some(ShiftRegister::new(serial, clock, latch));
+    }
+
+    /// Set the shift register pins of the board using pins that were created
+    /// outside of the backend. The order is the same as for
+    /// `set_shift_register_pins`: serial, clock, latch.
+    pub fn set_shift_register_outputs(&mut self, ser: B::Output, clk: B::Output, lat: B::Output) {
+        println!("setting shift register outputs");
+        self.register = Some(ShiftRegister::new(ser, clk, lat));
+    }
+
+    /// Sets the shift register of the board. Use this to hand the board a
+    /// register that also has its output enable or clear pins set.
+    pub fn set_shift_register(&mut self, register: ShiftRegister<B::Output>) -> Result<(), BoardError> {
+        println!("setting shift register");
+        let mut register = register;
+        if register.has_output_enable() {
+            register.disable_outputs().map_err(BoardError::register)?;
+        }
+
+        self.register = Some(register);
+        self.latched = false;
+        return Ok(());
+    }
+
+    /// Sets the output enable pin of the shift register. The shift register
+    /// pins have to be set first. The outputs are held disabled until the
+    /// first byte with directions is latched onto the register.
+    pub fn set_output_enable_pin(&mut self, oe: u8) -> Result<(), BoardError> {
+        println!("setting output enable pin {:?}", oe);
+        let oe = self.board_type.to_bcm(oe).map_err(|e| BoardError::Backend(e.into()))?;
+        let pin = self.backend.output(oe).map_err(BoardError::Backend)?;
+        return self.set_output_enable_output(pin);
+    }
+
+    /// Sets an output enable pin that was created outside of the backend. The
+    /// outputs are held disabled until the first byte with directions is
+    /// latched onto the register.
+    pub fn set_output_enable_output(&mut self, oe: B::Output) -> Result<(), BoardError> {
+        let register = match self.register.as_mut() {
+            Some(r) => r,
+            None => return Err(BoardError::RegisterPinNotSet),
+        };
+
+        register.set_output_enable(oe);
+        if self.latched && !self.outputs_disabled {
+            register.enable_outputs().map_err(BoardError::register)?;
+        } else {
+            register.disable_outputs().map_err(BoardError::register)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Disables the outputs of the shift register using the output enable
+    /// pin. This blanks all eight direction inputs of the L293D chips at
+    /// once, without rewriting the register, so all motors stop.
+    pub fn disable_outputs(&mut self) -> Result<(), BoardError> {
+        println!("disabling shift register outputs");
+        let register = match self.register.as_mut() {
+            Some(r) => r,
+            None => return Err(BoardError::RegisterPinNotSet),
         };
+
+        register.disable_outputs().map_err(BoardError::register)?;
+        self.outputs_disabled = true;
+        return Ok(());
+    }
+
+    /// Enables the outputs of the shift register using the output enable pin.
+    /// If no byte has been latched yet, the outputs are enabled as soon as
+    /// the first byte is latched.
+    pub fn enable_outputs(&mut self) -> Result<(), BoardError> {
+        println!("enabling shift register outputs");
+        let register = match self.register.as_mut() {
+            Some(r) => r,
+            None => return Err(BoardError::RegisterPinNotSet),
+        };
+
+        if !register.has_output_enable() {
+            return Err(BoardError::OutputEnableNotSet);
+        }
+
+        self.outputs_disabled = false;
+        if self.latched {
+            register.enable_outputs().map_err(BoardError::register)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns whether the outputs of the shift register are currently
+    /// enabled.
+    pub fn outputs_enabled(&self) -> bool {
+        return match &self.register {
+            Some(r) => !r.has_output_enable() || (self.latched && !self.outputs_disabled),
+            None => false,
+        };
+    }
+
+    /// Sets the bytes of the 74HC595 registers that are daisy-chained after
+    /// the register of the shield, like registers for LEDs and relays. The
+    /// first byte is for the register that is connected to the shield. The
+    /// bytes are pushed together with the directions of the motors, since
+    /// all registers are latched at once.
+    pub fn set_chained_bytes(&mut self, bytes: &[u8]) -> Result<(), BoardError> {
+        println!("setting chained bytes {:?}", bytes);
+        self.chained = bytes.to_vec();
+        return self.update_shift_register();
+    }
+
+    /// Returns the bytes of the registers that are daisy-chained after the
+    /// register of the shield.
+    pub fn get_chained_bytes(&self) -> &[u8] {
+        return &self.chained;
     }
 
     /// Returns the directions of the board. This is the byte that contains
@@ -246,7 +497,8 @@ impl AMSBoard {
         return self.directions; 
     }
 
-    /// Inverts the direction of the given motor.
+    /// Inverts the direction of the given motor. An error of the shift
+    /// register can not be returned, so it is logged.
     pub fn invert_motor_direction(&mut self, m: usize) {
         println!("inverting motor directions");
         match &mut self.motors[m - 1] {
@@ -255,7 +507,9 @@ impl AMSBoard {
         };
 
         self.update_directions();
-        self.update_shift_register();
+        if let Err(e) = self.push_directions() {
+            println!("could not update shift register: {:?}", e);
+        }
     }
     
     /// Changes the specified motor to the specified direction.
@@ -265,8 +519,7 @@ impl AMSBoard {
             Some(motor) => {
                 motor.set_direction(d);
                 self.update_directions();
-                self.update_shift_register();
-                return Ok(());
+                return self.push_directions().map_err(BoardError::into_motor_error);
             },
             _ => return Err(MotorError::MotorNotFound),
         };
@@ -290,9 +543,127 @@ impl AMSBoard {
         return self.start_motor(n, MotorConfig::new_full());
     }
 
+    /// Runs a specified motor at a signed speed from -1.0 to 1.0. The sign
+    /// picks the direction, positive is clockwise, and the magnitude is the
+    /// duty cycle at the pwm frequency of the motor. A speed of 0.0 stops the
+    /// motor.
+    pub fn set_motor_speed(&mut self, n: usize, speed: f32) -> Result<(), BoardError> {
+        println!("set speed of motor {:?} to {:?}", n, speed);
+        let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+        let (direction, mc) = match MotorConfig::from_speed(speed, motor.get_pwm_period()) {
+            Some(c) => c,
+            None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+        };
+
+        if speed == 0.0 {
+            return self.stop_motor(n);
+        }
+
+        motor.set_direction(direction);
+        self.update_directions();
+        return self.start_motor(n, mc);
+    }
+
+    /// Changes the speed of a motor for a control loop that updates it on
+    /// every tick. While the motor keeps running in the same direction, only
+    /// the duty cycle of its enable is changed, so the directions are not
+    /// pushed again. A start, a stop or a reversal goes through
+    /// `set_motor_speed`.
+    pub(crate) fn update_motor_speed(&mut self, n: usize, speed: f32) -> Result<(), BoardError> {
+        let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+        let (direction, mc) = match MotorConfig::from_speed(speed, motor.get_pwm_period()) {
+            Some(c) => c,
+            None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+        };
+
+        if speed == 0.0 || motor.get_speed() == 0.0 || motor.get_state() != SlotState::Run(direction) {
+            return self.set_motor_speed(n, speed);
+        }
+
+        return motor.set_duty(mc).map_err(BoardError::MotorError);
+    }
+
+    /// Runs several motors at signed speeds at once, see `set_motor_speed`.
+    /// The directions of all motors are pushed onto the shift register in a
+    /// single latch before any motor is started, so the motors never
+    /// disagree about their directions. All speeds are checked before any
+    /// motor is changed.
+    pub fn set_motor_speeds(&mut self, speeds: &[(usize, f32)]) -> Result<(), BoardError> {
+        println!("set speeds of motors {:?}", speeds);
+        let mut configs = Vec::with_capacity(speeds.len());
+        for (n, speed) in speeds.iter() {
+            let motor = self.get_motor(*n).map_err(BoardError::MotorError)?;
+            match MotorConfig::from_speed(*speed, motor.get_pwm_period()) {
+                Some(c) => configs.push((*n, *speed, c)),
+                None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+            };
+        }
+
+        for (n, speed, (direction, _)) in configs.iter() {
+            let motor = self.get_motor(*n).map_err(BoardError::MotorError)?;
+            if *speed != 0.0 {
+                motor.set_direction(*direction);
+                motor.set_stop_mode(None);
+            }
+        }
+
+        self.update_directions();
+        self.push_directions()?;
+
+        for (n, speed, (_, mc)) in configs.iter() {
+            let motor = self.get_motor(*n).map_err(BoardError::MotorError)?;
+            match *speed == 0.0 {
+                true => motor.stop().map_err(BoardError::MotorError)?,
+                false => motor.start(*mc).map_err(BoardError::MotorError)?,
+            };
+        }
+
+        return Ok(());
+    }
+
+    /// Sets the states of the L293D inputs of several slots and pushes them
+    /// in a single latch, without changing the enables of the motors. This
+    /// drives the coils of a stepper motor.
+    pub(crate) fn set_slot_states(&mut self, states: &[(usize, SlotState)]) -> Result<(), BoardError> {
+        for (n, state) in states.iter() {
+            let motor = self.get_motor(*n).map_err(BoardError::MotorError)?;
+            match *state {
+                SlotState::Run(d) => {
+                    motor.set_direction(d);
+                    motor.set_stop_mode(None);
+                },
+                SlotState::Stopped(m) => motor.set_stop_mode(Some(m)),
+            };
+        }
+
+        self.update_directions();
+        return self.update_shift_register();
+    }
+
+    /// Runs a specified motor at a signed speed in percent, from -100 to
+    /// 100. See `set_motor_speed`.
+    pub fn set_motor_speed_percent(&mut self, n: usize, percent: f32) -> Result<(), BoardError> {
+        return self.set_motor_speed(n, percent / 100.0);
+    }
+
+    /// Returns the signed speed of a specified motor, from -1.0 to 1.0.
+    pub fn get_motor_speed(&mut self, n: usize) -> Result<f32, BoardError> {
+        let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+        return Ok(motor.get_speed());
+    }
+
+    /// Sets the pwm frequency of a specified motor in Hz. The motor is
+    /// stopped when its pin is claimed again with the new period.
+    pub fn set_motor_frequency(&mut self, n: usize, hz: u32) -> Result<(), BoardError> {
+        println!("set pwm frequency of motor {:?} to {:?} hz", n, hz);
+        let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+        motor.set_pwm_frequency(hz).map_err(BoardError::MotorError)?;
+        return Ok(());
+    }
+
     /// Stops a specified motor gracefully.
     pub fn stop_motor(&mut self, n: usize) -> Result<(), BoardError> { 
-        if n < 1 || n > 4 {
+        if !(1..=4).contains(&n) {
             return Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds));
         }
 
@@ -300,9 +671,38 @@ impl AMSBoard {
             return Err(BoardError::MotorError(MotorError::MotorNotFound));
         }
 
-        let motor: &mut Motor = self.motors[n - 1].as_mut().unwrap();
+        let motor: &mut Motor<B> = self.motors[n - 1].as_mut().unwrap();
         println!("stopping motor");
-        motor.stop();
+        motor.stop().map_err(BoardError::MotorError)?;
+
+        return Ok(());
+    }
+
+    /// Stops a specified motor in the specified way. In both ways the enable
+    /// is turned off before the L293D inputs of the slot are latched. Braking
+    /// sets both inputs high and then turns the enable fully on, so the motor
+    /// is stopped actively. Releasing sets both inputs low and leaves the
+    /// enable off, so the motor coasts to a stop. The slot keeps this state
+    /// in the direction byte until the motor is started again. If the inputs
+    /// can not be latched, the motor is left released with its previous
+    /// inputs.
+    pub fn stop_motor_with(&mut self, n: usize, mode: StopMode) -> Result<(), BoardError> {
+        println!("stopping motor {:?} with {:?}", n, mode);
+        let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+        let previous = motor.get_stop_mode();
+        motor.stop().map_err(BoardError::MotorError)?;
+        motor.set_stop_mode(Some(mode));
+        self.update_directions();
+        if let Err(e) = self.update_shift_register() {
+            self.get_motor(n).map_err(BoardError::MotorError)?.set_stop_mode(previous);
+            self.update_directions();
+            return Err(e);
+        }
+
+        if let StopMode::Brake = mode {
+            let motor = self.get_motor(n).map_err(BoardError::MotorError)?;
+            motor.stop_with(mode).map_err(BoardError::MotorError)?;
+        }
 
         return Ok(());
     }
@@ -311,7 +711,7 @@ impl AMSBoard {
     /// The motor should first be set on the board before a test can be
     /// executed.
     pub fn test_motor_range(&mut self, n: usize, cycle:Duration, low:Duration, up:Duration, step:Duration) {
-        if n < 1 || n > 4 {
+        if !(1..=4).contains(&n) {
             panic!("index out of bounds");
         }
 
@@ -319,25 +719,74 @@ impl AMSBoard {
             panic!("motor is not set");
         }
 
-        let motor: &mut Motor = self.motors[n - 1].as_mut().unwrap();
+        let motor: &mut Motor<B> = self.motors[n - 1].as_mut().unwrap();
         println!("testing motor {:?} range", n);
         motor.test_range(cycle, low, up, step);
     }
 }
 
+#[derive(Debug)]
 pub enum BoardError {
     RegisterPinNotSet,
+    OutputEnableNotSet,
+    WiringPinNotSet(ArduinoPin),
+    ShieldIndexOutOfBounds,
     MotorError(MotorError),
+    ServoError(ServoError),
+    Backend(BackendError),
+}
+
+impl BoardError {
+    /// Converts the error of a shift register pin into a board error.
+    pub(crate) fn pin<E: embedded_hal::digital::Error>(e: E) -> BoardError {
+        return BoardError::Backend(BackendError::digital(e));
+    }
+
+    /// Converts an error of the shift register into the error of a method
+    /// that returns a motor error.
+    fn into_motor_error(self) -> MotorError {
+        return match self {
+            BoardError::MotorError(e) => e,
+            BoardError::Backend(e) => MotorError::Backend(e),
+            _ => MotorError::Backend(BackendError::Unsupported),
+        };
+    }
+
+    /// Converts an error of the shift register into a board error.
+    fn register<E: embedded_hal::digital::Error>(e: ShiftRegisterError<E>) -> BoardError {
+        return match e {
+            ShiftRegisterError::PinNotSet => BoardError::OutputEnableNotSet,
+            ShiftRegisterError::Pin(e) => BoardError::pin(e),
+        };
+    }
 }
 
+/// The numbering of the pins that are passed to the board, motors and
+/// servos. `BCM` uses the gpio numbers of the Broadcom chip, `Board` uses
+/// the physical pin numbers of the 40 pin header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BoardType {
     BCM,
     Board
 }
 
+impl BoardType {
+    /// Returns the BCM number of the specified pin in this numbering. Power
+    /// and ground pins of the header are not a gpio and return an error.
+    pub fn to_bcm(&self, pin: u8) -> Result<u8, PinError> {
+        return match self {
+            BoardType::BCM => Ok(pin),
+            BoardType::Board => pins::bcm_from_header(pin),
+        };
+    }
+}
+
 #[cfg(test)]
+#[allow(unused_mut, unused_must_use, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::motor::Status;
 
     #[test]
     fn set_m1direction_test() {
@@ -446,10 +895,11 @@ mod tests {
 
     #[test]
     fn motor_is_running_test() {
-        let mut board = AMSBoard::new(BoardType::BCM);
-        let mut motor = Motor::new();
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
 
-        motor.set_pin(16);
+        motor.set_pin(21);
         board.set_shift_register_pins(16, 20, 19);
         board.set_motor(motor, 1);
         board.start_motor_full(1);
@@ -496,8 +946,9 @@ mod tests {
 
     #[test]
     fn test_motor_range() {
-        let mut board = AMSBoard::new(BoardType::BCM);
-        let mut motor = Motor::new();
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
         motor.set_pin(16);
         board.set_motor(motor, 1);
         let cycle = Duration::from_millis(20);
@@ -505,17 +956,309 @@ mod tests {
         let up = Duration::from_millis(20);
         let step = Duration::from_millis(2);
         board.test_motor_range(1, cycle, low, up, step);
+        assert_eq!(9, backend.actions(16).len());
     }
 
     #[test]
     fn motor_direction_invert_test() {
-        let mut board = AMSBoard::new(BoardType::BCM);
-        let mut motor = Motor::new();
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend);
         motor.set_pin(16);
         board.set_motor(motor, 1);
         board.invert_motor_direction(1);
-        let m: &Motor = board.get_motor(1).unwrap();
+        let m: &Motor<MemoryBackend> = board.get_motor(1).unwrap();
         assert!(matches!(m.get_direction(), Direction::Counterclockwise));
     }
-}
 
+    #[test]
+    fn shift_register_traffic_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 1).unwrap();
+        board.change_motor_direction(1, Direction::Counterclockwise).unwrap();
+
+        // the latch is only opened once all bits are pushed
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+
+        // every bit is pushed on a rising edge of the clock
+        let mut clock = Vec::new();
+        for _ in 0..8 {
+            clock.push(PinAction::Low);
+            clock.push(PinAction::High);
+        }
+        assert_eq!(clock, backend.actions(20));
+
+        // motor 1 counterclockwise is 0b00001000, pushed from MSB to LSB
+        let mut serial = vec![PinAction::Low; 8];
+        serial[4] = PinAction::High;
+        assert_eq!(serial, backend.actions(16));
+
+        backend.clear();
+        board.start_motor_full(1).unwrap();
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+    }
+
+    #[test]
+    fn embedded_hal_mock_test() {
+        use crate::backend::HalPins;
+        use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+        use embedded_hal_mock::eh1::pwm::{Mock as PwmMock, Transaction as PwmTransaction};
+
+        // motor 3 clockwise is 0b00000001, pushed from MSB to LSB
+        let mut serial_expectations = vec![PinTransaction::set(State::Low); 7];
+        serial_expectations.push(PinTransaction::set(State::High));
+        let mut clock_expectations = Vec::new();
+        for _ in 0..8 {
+            clock_expectations.push(PinTransaction::set(State::Low));
+            clock_expectations.push(PinTransaction::set(State::High));
+        }
+        let latch_expectations = [PinTransaction::set(State::Low), PinTransaction::set(State::High)];
+        let pwm_expectations = [
+            PwmTransaction::max_duty_cycle(100),
+            PwmTransaction::set_duty_cycle(100),
+            PwmTransaction::set_duty_cycle(0),
+        ];
+
+        let mut serial = PinMock::new(&serial_expectations);
+        let mut clock = PinMock::new(&clock_expectations);
+        let mut latch = PinMock::new(&latch_expectations);
+        let mut pwm = PwmMock::new(&pwm_expectations);
+
+        let mut board = AMSBoard::with_backend(BoardType::BCM, HalPins::new());
+        board.set_shift_register_outputs(serial.clone(), clock.clone(), latch.clone());
+        let mut motor = Motor::with_backend(HalPins::new());
+        motor.set_pwm_pin(pwm.clone());
+        board.set_motor(motor, 3).unwrap();
+        board.start_motor_full(3).unwrap();
+        board.stop_motor(3).unwrap();
+
+        serial.done();
+        clock.done();
+        latch.done();
+        pwm.done();
+    }
+
+    #[test]
+    fn chained_registers_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 3).unwrap();
+        board.set_chained_bytes(&[0b10000000]).unwrap();
+        assert_eq!(&[0b10000000], board.get_chained_bytes());
+
+        // the chained byte is shifted first, then motor 3 clockwise
+        let mut serial = vec![PinAction::High];
+        serial.extend(vec![PinAction::Low; 14]);
+        serial.push(PinAction::High);
+        assert_eq!(serial, backend.actions(16));
+        assert_eq!(32, backend.actions(20).len());
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+    }
+
+    #[test]
+    fn output_enable_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        assert!(board.set_output_enable_pin(26).is_err());
+        board.set_shift_register_pins(16, 20, 19);
+        assert!(matches!(board.disable_outputs(), Err(BoardError::OutputEnableNotSet)));
+
+        // the outputs are held disabled until the first byte is latched
+        board.set_output_enable_pin(26).unwrap();
+        assert_eq!(vec![PinAction::High], backend.actions(26));
+        assert!(!board.outputs_enabled());
+        board.set_motor(motor, 1).unwrap();
+        board.start_motor_full(1).unwrap();
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(26));
+        assert!(board.outputs_enabled());
+
+        // disabling does not rewrite the register
+        backend.clear();
+        board.disable_outputs().unwrap();
+        assert!(!board.outputs_enabled());
+        board.enable_outputs().unwrap();
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(26));
+        assert!(backend.actions(19).is_empty());
+    }
+
+    #[test]
+    fn board_numbering_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::Board, backend.clone());
+        board.set_shift_register_pins(36, 38, 35);
+        board.set_motor(Motor::with_backend(backend.clone()), 1).unwrap();
+        board.get_motor(1).unwrap().set_pin(40).unwrap();
+        board.start_motor_full(1).unwrap();
+
+        // the header pins are claimed by their BCM number
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+        assert_eq!(16, backend.actions(20).len());
+        assert!(backend.actions(40).is_empty());
+
+        // power and ground pins are not a gpio
+        let motor = board.get_motor(1).unwrap();
+        assert!(matches!(motor.set_pin(2), Err(BackendError::InvalidPin(PinError::Power(2)))));
+        assert!(matches!(motor.set_pin(39), Err(BackendError::InvalidPin(PinError::Ground(39)))));
+        assert!(matches!(board.set_output_enable_pin(6),
+            Err(BoardError::Backend(BackendError::InvalidPin(PinError::Ground(6))))));
+    }
+
+    #[test]
+    fn from_wiring_test() {
+        let wiring = ShieldWiring::new()
+            .connect(ArduinoPin::D8, 16)
+            .connect(ArduinoPin::D4, 20)
+            .connect(ArduinoPin::D12, 19)
+            .connect(ArduinoPin::D7, 26)
+            .connect(ArduinoPin::D11, 21)
+            .connect(ArduinoPin::D3, 5)
+            .connect(ArduinoPin::D6, 6)
+            .connect(ArduinoPin::D5, 13)
+            .connect(ArduinoPin::D9, 12)
+            .connect(ArduinoPin::D10, 18);
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::from_wiring(BoardType::BCM, backend.clone(), &wiring).unwrap();
+        for slot in 1..=4 {
+            assert!(board.get_motor(slot).is_ok());
+        }
+        assert!(board.get_servo(1).is_ok());
+        assert!(board.get_servo(2).is_ok());
+
+        board.start_motor_full(3).unwrap();
+        assert_eq!(vec![PinAction::High], backend.actions(6));
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(26));
+        assert_eq!(1, backend.actions(12).len());
+
+        // the shift register pins are required
+        let wiring = ShieldWiring::new().connect(ArduinoPin::D8, 16);
+        let result = AMSBoard::from_wiring(BoardType::BCM, MemoryBackend::new(), &wiring);
+        assert!(matches!(result, Err(BoardError::WiringPinNotSet(ArduinoPin::D4))));
+    }
+
+    #[test]
+    fn motor_speed_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 2).unwrap();
+        board.set_motor_frequency(2, 1000).unwrap();
+
+        board.set_motor_speed(2, -0.25).unwrap();
+        assert_eq!(16, board.get_directions());
+        assert_eq!(-0.25, board.get_motor_speed(2).unwrap());
+        let pwm = PinAction::Pwm { period: Duration::from_millis(1), pulse_width: Duration::from_micros(250) };
+        assert_eq!(vec![pwm], backend.actions(21));
+
+        board.set_motor_speed_percent(2, 100.0).unwrap();
+        assert_eq!(2, board.get_directions());
+        assert_eq!(1.0, board.get_motor_speed(2).unwrap());
+        board.set_motor_speed(2, 0.0).unwrap();
+        assert_eq!(vec![pwm, PinAction::High, PinAction::Low], backend.actions(21));
+        assert_eq!(0.0, board.get_motor_speed(2).unwrap());
+
+        assert!(matches!(board.set_motor_speed(2, 1.01), Err(BoardError::MotorError(MotorError::InvalidSpeed))));
+        assert!(matches!(board.set_motor_speed(2, f32::NAN), Err(BoardError::MotorError(MotorError::InvalidSpeed))));
+        assert!(matches!(board.set_motor_frequency(2, 0), Err(BoardError::MotorError(MotorError::InvalidFrequency))));
+        assert!(matches!(board.start_motor_pwm(2, Duration::from_millis(1), Duration::from_millis(2)),
+            Err(BoardError::MotorError(MotorError::PWMDurationTooHigh))));
+    }
+
+    #[test]
+    fn stop_mode_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 1).unwrap();
+        board.set_motor(Motor::with_backend(backend.clone()), 2).unwrap();
+        board.start_motor_full(1).unwrap();
+
+        // braking turns the enable off, sets both inputs of M1 high and then
+        // turns the enable on
+        board.stop_motor_with(1, StopMode::Brake).unwrap();
+        assert_eq!(0b00001110, board.get_directions());
+        assert_eq!(vec![PinAction::High, PinAction::Low, PinAction::High], backend.actions(21));
+        assert!(matches!(board.get_motor(1).unwrap().get_status(), Status::Braking));
+        assert!(!board.get_motor(1).unwrap().is_running());
+
+        // releasing turns the enable off and sets both inputs low
+        board.stop_motor_with(1, StopMode::Release).unwrap();
+        assert_eq!(0b00000010, board.get_directions());
+        assert_eq!(PinAction::Low, *backend.actions(21).last().unwrap());
+
+        // starting again restores the direction
+        board.start_motor_full(1).unwrap();
+        assert_eq!(0b00000110, board.get_directions());
+        assert!(board.stop_motor_with(3, StopMode::Brake).is_err());
+
+        // a brake that can not be latched leaves the motor released with its
+        // direction on the inputs
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_motor(motor, 1).unwrap();
+        board.start_motor_full(1).unwrap();
+        assert!(matches!(board.stop_motor_with(1, StopMode::Brake), Err(BoardError::RegisterPinNotSet)));
+        assert_eq!(None, board.get_motor(1).unwrap().get_stop_mode());
+        assert_eq!(0b00000100, board.get_directions());
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(21));
+    }
+
+    #[test]
+    fn inverted_slot_test() {
+        let mut board = AMSBoard::new(BoardType::BCM);
+        board.set_motor(Motor::new(), 1).unwrap();
+        board.set_motor(Motor::new(), 2).unwrap();
+        board.set_slot_inverted(2, true).unwrap();
+        assert_eq!(0b00010100, board.get_directions());
+
+        // the flag stays with the slot when the motor is replaced
+        let mut motor = Motor::new();
+        motor.set_direction(Direction::Counterclockwise);
+        board.set_motor(motor, 2).unwrap();
+        assert_eq!(0b00000110, board.get_directions());
+        assert!(board.is_slot_inverted(2).unwrap());
+        assert!(!board.is_slot_inverted(1).unwrap());
+        assert!(matches!(board.get_motor(2).unwrap().get_direction(), Direction::Counterclockwise));
+        assert!(board.set_slot_inverted(5, true).is_err());
+
+        let wiring = ShieldWiring::new()
+            .connect(ArduinoPin::D8, 16)
+            .connect(ArduinoPin::D4, 20)
+            .connect(ArduinoPin::D12, 19)
+            .connect(ArduinoPin::D3, 5)
+            .invert(2);
+        let board = AMSBoard::from_wiring(BoardType::BCM, MemoryBackend::new(), &wiring).unwrap();
+        assert_eq!(16, board.get_directions());
+    }
+
+    #[test]
+    fn attach_encoder_test() {
+        let backend = MemoryBackend::new();
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let encoder = Encoder::with_backend(BoardType::BCM, &backend, 22, 23).unwrap();
+        board.attach_encoder(1, encoder).unwrap();
+        assert!(matches!(board.get_encoder(2), Err(MotorError::EncoderNotFound)));
+        assert!(matches!(board.attach_encoder(5, Encoder::with_backend(BoardType::BCM, &backend, 5, 6).unwrap()),
+            Err(MotorError::MotorIndexOutOfBounds)));
+
+        backend.feed_quadrature(22, 23, 4);
+        assert_eq!(4, board.get_encoder(1).unwrap().count());
+        assert!(board.detach_encoder(1).unwrap().is_some());
+        assert!(board.get_encoder(1).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::BoardError;
+use crate::motor::MotorError;
+use crate::shared::SharedBoard;
+use motor_shield_core::drive;
+
+/// # A two-wheeled robot
+///
+/// The differential drive wraps a shared board and the two slots of the left
+/// and right wheels. Positive speeds drive the robot forward, which is the
+/// clockwise direction of both slots. A wheel that is mounted mirrored can be
+/// fixed with `AMSBoard::set_slot_inverted`. Inputs within the deadband are
+/// ignored, and all outputs are scaled by the maximum output.
+pub struct DifferentialDrive<B: GpioBackend = RppalBackend> {
+    /// the board that the wheels are on
+    board: SharedBoard<B>,
+    /// the slot of the left wheel
+    left: usize,
+    /// the slot of the right wheel
+    right: usize,
+    /// the inputs around zero that are ignored
+    deadband: f32,
+    /// the factor that all outputs are scaled by
+    max_output: f32,
+}
+
+impl<B: GpioBackend> DifferentialDrive<B> {
+    /// The deadband that is used until it is set.
+    pub const DEFAULT_DEADBAND: f32 = 0.02;
+
+    /// Creates a new drive on the specified slots of the board. Both slots
+    /// need a motor, and the wheels can not share a slot.
+    pub fn new(board: &SharedBoard<B>, left: usize, right: usize) -> Result<DifferentialDrive<B>, BoardError> {
+        if left == right {
+            return Err(BoardError::MotorError(MotorError::DuplicateSlot));
+        }
+        {
+            let mut board = board.lock();
+            board.get_motor(left).map_err(BoardError::MotorError)?;
+            board.get_motor(right).map_err(BoardError::MotorError)?;
+        }
+
+        println!("creating differential drive on slots {:?} and {:?}", left, right);
+        return Ok(DifferentialDrive {
+            board: board.clone(),
+            left,
+            right,
+            deadband: Self::DEFAULT_DEADBAND,
+            max_output: 1.0,
+        });
+    }
+
+    /// Sets the inputs around zero that are ignored, from 0.0 to 1.0.
+    pub fn set_deadband(&mut self, deadband: f32) {
+        self.deadband = deadband.clamp(0.0, 1.0);
+    }
+
+    /// Sets the factor that all outputs are scaled by, from 0.0 to 1.0. This
+    /// can be used to limit the top speed of the robot.
+    pub fn set_max_output(&mut self, max_output: f32) {
+        self.max_output = max_output.clamp(0.0, 1.0);
+    }
+
+    /// Returns the shared board of the drive.
+    pub fn board(&self) -> SharedBoard<B> {
+        return self.board.clone();
+    }
+
+    /// Drives the left and right wheels at the specified speeds.
+    pub fn tank(&mut self, left: f32, right: f32) -> Result<(), BoardError> {
+        let left = drive::apply_deadband(drive::clamp_speed(left), self.deadband);
+        let right = drive::apply_deadband(drive::clamp_speed(right), self.deadband);
+        return self.write(left, right);
+    }
+
+    /// Drives forward with the throttle while turning clockwise with the
+    /// turn, seen from above. The turn is the difference in speed between
+    /// the wheels.
+    pub fn arcade(&mut self, throttle: f32, turn: f32) -> Result<(), BoardError> {
+        let throttle = drive::apply_deadband(drive::clamp_speed(throttle), self.deadband);
+        let turn = drive::apply_deadband(drive::clamp_speed(turn), self.deadband);
+        let (left, right) = drive::arcade(throttle, turn);
+        return self.write(left, right);
+    }
+
+    /// Drives forward with the throttle along a curve. Unlike arcade driving,
+    /// the curvature sets how sharp the robot turns regardless of its speed,
+    /// so the robot does not turn while it stands still.
+    pub fn curvature(&mut self, throttle: f32, curvature: f32) -> Result<(), BoardError> {
+        let throttle = drive::apply_deadband(drive::clamp_speed(throttle), self.deadband);
+        let curvature = drive::clamp_speed(curvature);
+        let (left, right) = drive::curvature(throttle, curvature);
+        return self.write(left, right);
+    }
+
+    /// Stops both wheels.
+    pub fn stop(&mut self) -> Result<(), BoardError> {
+        return self.write(0.0, 0.0);
+    }
+
+    /// Writes the speeds of both wheels, scaled by the maximum output, in a
+    /// single latch.
+    fn write(&mut self, left: f32, right: f32) -> Result<(), BoardError> {
+        let left = drive::clamp_speed(left * self.max_output);
+        let right = drive::clamp_speed(right * self.max_output);
+        println!("driving left={:?} right={:?}", left, right);
+        return self.board.lock().set_motor_speeds(&[(self.left, left), (self.right, right)]);
+    }
+}
+
+/// # A four-wheeled holonomic robot
+///
+/// The mecanum drive uses all four slots of a shared board, one for every
+/// wheel of a mecanum chassis. It can drive in any direction while it
+/// rotates. By default M1 is the front left wheel, M2 the front right, M3 the
+/// rear left and M4 the rear right wheel. The speeds of the wheels are scaled
+/// down together when one of them would be faster than full speed, and all
+/// four directions are latched at once, so the wheels never disagree.
+pub struct MecanumDrive<B: GpioBackend = RppalBackend> {
+    /// the board that the wheels are on
+    board: SharedBoard<B>,
+    /// the slots of the front left, front right, rear left and rear right
+    /// wheels
+    slots: [usize; 4],
+    /// the inputs around zero that are ignored
+    deadband: f32,
+    /// the factor that all outputs are scaled by
+    max_output: f32,
+}
+
+impl<B: GpioBackend> MecanumDrive<B> {
+    /// Creates a new drive with the wheels on slots M1 to M4 of the board.
+    pub fn new(board: &SharedBoard<B>) -> Result<MecanumDrive<B>, BoardError> {
+        return MecanumDrive::with_slots(board, [1, 2, 3, 4]);
+    }
+
+    /// Creates a new drive with the front left, front right, rear left and
+    /// rear right wheels on the specified slots. Every slot needs a motor,
+    /// and every wheel needs a slot of its own.
+    pub fn with_slots(board: &SharedBoard<B>, slots: [usize; 4]) -> Result<MecanumDrive<B>, BoardError> {
+        {
+            let mut board = board.lock();
+            for (i, slot) in slots.iter().enumerate() {
+                if slots[..i].contains(slot) {
+                    return Err(BoardError::MotorError(MotorError::DuplicateSlot));
+                }
+                board.get_motor(*slot).map_err(BoardError::MotorError)?;
+            }
+        }
+
+        println!("creating mecanum drive on slots {:?}", slots);
+        return Ok(MecanumDrive {
+            board: board.clone(),
+            slots,
+            deadband: DifferentialDrive::<B>::DEFAULT_DEADBAND,
+            max_output: 1.0,
+        });
+    }
+
+    /// Sets the inputs around zero that are ignored, from 0.0 to 1.0.
+    pub fn set_deadband(&mut self, deadband: f32) {
+        self.deadband = deadband.clamp(0.0, 1.0);
+    }
+
+    /// Sets the factor that all outputs are scaled by, from 0.0 to 1.0.
+    pub fn set_max_output(&mut self, max_output: f32) {
+        self.max_output = max_output.clamp(0.0, 1.0);
+    }
+
+    /// Returns the shared board of the drive.
+    pub fn board(&self) -> SharedBoard<B> {
+        return self.board.clone();
+    }
+
+    /// Drives forward with vx and to the right with vy, while rotating
+    /// clockwise with omega, seen from above. All values are from -1.0 to
+    /// 1.0.
+    pub fn drive(&mut self, vx: f32, vy: f32, omega: f32) -> Result<(), BoardError> {
+        let vx = drive::apply_deadband(drive::clamp_speed(vx), self.deadband);
+        let vy = drive::apply_deadband(drive::clamp_speed(vy), self.deadband);
+        let omega = drive::apply_deadband(drive::clamp_speed(omega), self.deadband);
+        let speeds = drive::mecanum(vx, vy, omega);
+        return self.write(speeds);
+    }
+
+    /// Stops all wheels.
+    pub fn stop(&mut self) -> Result<(), BoardError> {
+        return self.write([0.0; 4]);
+    }
+
+    /// Writes the speeds of all wheels, scaled by the maximum output, in a
+    /// single latch.
+    fn write(&mut self, speeds: [f32; 4]) -> Result<(), BoardError> {
+        let mut pairs = [(0, 0.0); 4];
+        for (i, speed) in speeds.iter().enumerate() {
+            pairs[i] = (self.slots[i], drive::clamp_speed(speed * self.max_output));
+        }
+
+        println!("driving wheels {:?}", pairs);
+        return self.board.lock().set_motor_speeds(&pairs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::board::{AMSBoard, BoardType};
+    use crate::motor::Motor;
+
+    fn drive(backend: &MemoryBackend) -> DifferentialDrive<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        board.set_shift_register_pins(16, 20, 19);
+        for (slot, pin) in [(1, 21), (2, 26)] {
+            let mut motor = Motor::with_backend(backend.clone());
+            motor.set_pin(pin).unwrap();
+            board.set_motor(motor, slot).unwrap();
+            board.set_motor_frequency(slot, 1000).unwrap();
+        }
+        return DifferentialDrive::new(&SharedBoard::new(board), 1, 2).unwrap();
+    }
+
+    fn pulse_widths(backend: &MemoryBackend, pin: u8) -> Vec<i64> {
+        return backend.actions(pin).iter().map(|a| match a {
+            PinAction::Pwm { pulse_width, .. } => pulse_width.as_micros() as i64,
+            PinAction::High => 1000,
+            PinAction::Low => 0,
+        }).collect();
+    }
+
+    #[test]
+    fn tank_test() {
+        let backend = MemoryBackend::new();
+        let mut drive = drive(&backend);
+        drive.set_deadband(0.0);
+        drive.tank(0.5, -1.5).unwrap();
+        assert_eq!(vec![500], pulse_widths(&backend, 21));
+        assert_eq!(vec![1000], pulse_widths(&backend, 26));
+        assert_eq!(0b00010100, drive.board().lock().get_directions());
+
+        // both directions are latched at once
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+    }
+
+    #[test]
+    fn arcade_and_curvature_test() {
+        let backend = MemoryBackend::new();
+        let mut drive = drive(&backend);
+        drive.set_deadband(0.1);
+        // the turn is within the deadband and the throttle is rescaled
+        drive.arcade(0.5, 0.05).unwrap();
+        assert_eq!(vec![444], pulse_widths(&backend, 21));
+        assert_eq!(vec![444], pulse_widths(&backend, 26));
+        assert_eq!(0b00000110, drive.board().lock().get_directions());
+
+        backend.clear();
+        drive.set_max_output(0.5);
+        drive.curvature(1.0, 0.5).unwrap();
+        assert_eq!(vec![500], pulse_widths(&backend, 21));
+        assert_eq!(vec![166], pulse_widths(&backend, 26));
+
+        backend.clear();
+        drive.stop().unwrap();
+        assert_eq!(vec![0], pulse_widths(&backend, 21));
+        assert_eq!(vec![0], pulse_widths(&backend, 26));
+    }
+
+    #[test]
+    fn invalid_slots_test() {
+        let board = SharedBoard::new(AMSBoard::with_backend(BoardType::BCM, MemoryBackend::new()));
+        assert!(matches!(DifferentialDrive::new(&board, 1, 2), Err(BoardError::MotorError(MotorError::MotorNotFound))));
+        let board = drive(&MemoryBackend::new()).board();
+        assert!(matches!(DifferentialDrive::new(&board, 2, 2), Err(BoardError::MotorError(MotorError::DuplicateSlot))));
+    }
+
+    fn mecanum(backend: &MemoryBackend) -> MecanumDrive<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        board.set_shift_register_pins(16, 20, 19);
+        for (slot, pin) in [(1, 21), (2, 26), (3, 13), (4, 12)] {
+            let mut motor = Motor::with_backend(backend.clone());
+            motor.set_pin(pin).unwrap();
+            board.set_motor(motor, slot).unwrap();
+            board.set_motor_frequency(slot, 1000).unwrap();
+        }
+        let mut drive = MecanumDrive::new(&SharedBoard::new(board)).unwrap();
+        drive.set_deadband(0.0);
+        return drive;
+    }
+
+    #[test]
+    fn mecanum_strafe_test() {
+        let backend = MemoryBackend::new();
+        let mut drive = mecanum(&backend);
+        drive.drive(0.0, 0.5, 0.0).unwrap();
+        for pin in [21, 26, 13, 12] {
+            assert_eq!(vec![500], pulse_widths(&backend, pin));
+        }
+
+        // M1 and M4 run clockwise, M2 and M3 counterclockwise
+        assert_eq!(4 | 16 | 64 | 32, drive.board().lock().get_directions());
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+    }
+
+    #[test]
+    fn mecanum_normalizes_test() {
+        let backend = MemoryBackend::new();
+        let mut drive = mecanum(&backend);
+        drive.drive(1.0, 0.0, 1.0).unwrap();
+        assert_eq!(vec![1000], pulse_widths(&backend, 21));
+        assert_eq!(vec![0], pulse_widths(&backend, 26));
+        assert_eq!(vec![1000], pulse_widths(&backend, 13));
+        assert_eq!(vec![0], pulse_widths(&backend, 12));
+
+        backend.clear();
+        drive.stop().unwrap();
+        for pin in [21, 26, 13, 12] {
+            assert_eq!(vec![0], pulse_widths(&backend, pin));
+        }
+    }
+
+    #[test]
+    fn mecanum_invalid_slots_test() {
+        let backend = MemoryBackend::new();
+        let board = mecanum(&backend).board();
+        assert!(matches!(MecanumDrive::with_slots(&board, [1, 2, 3, 3]), Err(BoardError::MotorError(MotorError::DuplicateSlot))));
+        assert!(matches!(MecanumDrive::with_slots(&board, [1, 2, 3, 5]), Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds))));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::backend::{BackendError, EdgeInput, InputBackend, RppalBackend};
+use crate::board::BoardType;
+use crate::motor::Direction;
+use motor_shield_core::encoder::{Quadrature, Velocity};
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// # A quadrature encoder
+///
+/// The encoder reads the A and B signals of a motor encoder on two input
+/// pins and counts every edge of either signal. Forward, where A leads B,
+/// counts up. When the backend has interrupts, the counts are kept up to
+/// date in the background. Otherwise the encoder has to be polled often
+/// enough to see every edge. The encoder can be attached to a motor slot of
+/// the board with `AMSBoard::attach_encoder`.
+pub struct Encoder {
+    /// the input pin of the A signal
+    a: Box<dyn EdgeInput + Send>,
+    /// the input pin of the B signal
+    b: Box<dyn EdgeInput + Send>,
+    /// the decoder that is shared with the edge callbacks
+    state: Arc<Mutex<EncoderState>>,
+    /// whether the edges are reported by interrupts
+    interrupts: bool,
+    /// the number of counts in one revolution of the output shaft
+    counts_per_revolution: u32,
+    /// whether forward counts down
+    inverted: bool,
+    /// the time without counts after which the encoder is standing still
+    timeout: Duration,
+}
+
+/// The decoder of an encoder and the last levels of its signals.
+struct EncoderState {
+    /// the decoder of the signals
+    decoder: Quadrature,
+    /// the last level of A
+    a: bool,
+    /// the last level of B
+    b: bool,
+    /// the step of the last count
+    last_step: i8,
+    /// the estimator of the velocity
+    velocity: Velocity,
+    /// the moment that the times of the velocity are relative to
+    epoch: Instant,
+}
+
+impl EncoderState {
+    /// Updates the decoder with the current levels of A and B.
+    fn update(&mut self, a: bool, b: bool) {
+        self.a = a;
+        self.b = b;
+        let step = self.decoder.update(a, b);
+        if step != 0 {
+            self.last_step = step;
+            let at = self.epoch.elapsed();
+            self.velocity.record(at, self.decoder.count());
+        }
+    }
+}
+
+impl Encoder {
+    /// The time without counts after which the encoder is standing still
+    /// that is used until it is set.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Creates a new encoder on the specified pins using rppal. The pins are
+    /// interpreted using the board type.
+    pub fn new(t: BoardType, a: u8, b: u8) -> Result<Encoder, BackendError> {
+        return Encoder::with_backend(t, &RppalBackend, a, b);
+    }
+
+    /// Creates a new encoder on the specified pins of the backend.
+    pub fn with_backend<I: InputBackend>(t: BoardType, backend: &I, a: u8, b: u8) -> Result<Encoder, BackendError>
+    where I::Input: Send + 'static {
+        println!("creating encoder on pins a={:?} b={:?}", a, b);
+        let a = backend.input(t.to_bcm(a)?)?;
+        let b = backend.input(t.to_bcm(b)?)?;
+        return Encoder::from_inputs(a, b);
+    }
+
+    /// Creates a new encoder on input pins that were created outside of a
+    /// backend. When either pin can not report its edges, the encoder is
+    /// polled.
+    pub fn from_inputs<P: EdgeInput + Send + 'static>(a: P, b: P) -> Result<Encoder, BackendError> {
+        let mut a = a;
+        let mut b = b;
+        let (la, lb) = (a.is_high()?, b.is_high()?);
+        let state = Arc::new(Mutex::new(EncoderState {
+            decoder: Quadrature::new(la, lb),
+            a: la,
+            b: lb,
+            last_step: 0,
+            velocity: Velocity::new(),
+            epoch: Instant::now(),
+        }));
+
+        let shared = Arc::clone(&state);
+        let interrupts = match a.on_edge(Box::new(move |high| {
+            let mut s = shared.lock().unwrap();
+            let b = s.b;
+            s.update(high, b);
+        })) {
+            Ok(()) => true,
+            Err(BackendError::Unsupported) => false,
+            Err(e) => return Err(e),
+        };
+
+        if interrupts {
+            let shared = Arc::clone(&state);
+            b.on_edge(Box::new(move |high| {
+                let mut s = shared.lock().unwrap();
+                let a = s.a;
+                s.update(a, high);
+            }))?;
+        }
+
+        return Ok(Encoder {
+            a: Box::new(a),
+            b: Box::new(b),
+            state,
+            interrupts,
+            counts_per_revolution: 0,
+            inverted: false,
+            timeout: Self::DEFAULT_TIMEOUT,
+        });
+    }
+
+    /// Returns whether the edges are reported by interrupts. If not, `poll`
+    /// has to be called at least once between two edges.
+    pub fn is_interrupt_driven(&self) -> bool {
+        return self.interrupts;
+    }
+
+    /// Reads both pins and counts the edge since the last read. This is not
+    /// needed when the encoder is interrupt driven, but it does no harm.
+    pub fn poll(&mut self) -> Result<(), BackendError> {
+        let a = self.a.is_high()?;
+        let b = self.b.is_high()?;
+        self.state.lock().unwrap().update(a, b);
+        return Ok(());
+    }
+
+    /// Sets the number of counts in one revolution of the output shaft,
+    /// which is four times the number of lines of the encoder times the
+    /// gear ratio of the motor.
+    pub fn set_counts_per_revolution(&mut self, counts: u32) {
+        self.counts_per_revolution = counts;
+    }
+
+    /// Returns the number of counts in one revolution of the output shaft,
+    /// or zero if it was not set.
+    pub fn get_counts_per_revolution(&self) -> u32 {
+        return self.counts_per_revolution;
+    }
+
+    /// Sets whether forward counts down, for an encoder that is mounted the
+    /// other way around.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    /// Sets the time without counts after which the encoder is standing
+    /// still.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the number of counts since the encoder was created or reset.
+    pub fn count(&self) -> i64 {
+        return self.sign() * self.state.lock().unwrap().decoder.count();
+    }
+
+    /// Sets the number of counts back to zero.
+    pub fn reset(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.decoder.set_count(0);
+        state.velocity.clear();
+    }
+
+    /// Returns the number of edges that were missed, because both signals
+    /// changed at once.
+    pub fn errors(&self) -> u32 {
+        return self.state.lock().unwrap().decoder.errors();
+    }
+
+    /// Returns the direction of the last count, or `None` if the encoder did
+    /// not count yet. Counting up is clockwise.
+    pub fn direction(&self) -> Option<Direction> {
+        let step = self.sign() * self.state.lock().unwrap().last_step as i64;
+        return match step {
+            1 => Some(Direction::Clockwise),
+            -1 => Some(Direction::Counterclockwise),
+            _ => None,
+        };
+    }
+
+    /// Returns the velocity in counts per second, which is zero when the
+    /// encoder did not count for longer than the timeout.
+    pub fn velocity(&self) -> f32 {
+        let state = self.state.lock().unwrap();
+        let now = state.epoch.elapsed();
+        return self.sign() as f32 * state.velocity.velocity(now, self.timeout);
+    }
+
+    /// Returns the velocity in revolutions per minute, or `None` if the
+    /// counts per revolution are not set.
+    pub fn rpm(&self) -> Option<f32> {
+        if self.counts_per_revolution == 0 {
+            return None;
+        }
+
+        return Some(self.velocity() * 60.0 / self.counts_per_revolution as f32);
+    }
+
+    /// Returns -1 if the encoder is inverted, and 1 if not.
+    fn sign(&self) -> i64 {
+        return match self.inverted {
+            true => -1,
+            false => 1,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{HalInput, MemoryBackend};
+    use embedded_hal_mock::eh1::digital::{Mock, State, Transaction};
+    use std::thread;
+
+    #[test]
+    fn counts_synthetic_edges_test() {
+        let backend = MemoryBackend::new();
+        let mut encoder = Encoder::with_backend(BoardType::BCM, &backend, 22, 23).unwrap();
+        assert!(encoder.is_interrupt_driven());
+        assert_eq!(None, encoder.direction());
+
+        backend.feed_quadrature(22, 23, 10);
+        assert_eq!(10, encoder.count());
+        assert_eq!(Some(Direction::Clockwise), encoder.direction());
+
+        backend.feed_quadrature(22, 23, -3);
+        assert_eq!(7, encoder.count());
+        assert_eq!(Some(Direction::Counterclockwise), encoder.direction());
+
+        encoder.set_inverted(true);
+        assert_eq!(-7, encoder.count());
+        assert_eq!(Some(Direction::Clockwise), encoder.direction());
+
+        encoder.reset();
+        assert_eq!(0, encoder.count());
+        assert_eq!(0, encoder.errors());
+    }
+
+    #[test]
+    fn velocity_test() {
+        let backend = MemoryBackend::new();
+        let mut encoder = Encoder::with_backend(BoardType::BCM, &backend, 22, 23).unwrap();
+        encoder.set_counts_per_revolution(120);
+        encoder.set_timeout(Duration::from_millis(20));
+        assert_eq!(None, Encoder::with_backend(BoardType::BCM, &backend, 5, 6).unwrap().rpm());
+
+        // a count at least every millisecond is at most 1000 counts per second
+        for _ in 0..10 {
+            backend.feed_quadrature(22, 23, -1);
+            thread::sleep(Duration::from_millis(1));
+        }
+        let velocity = encoder.velocity();
+        assert!((-1000.0..0.0).contains(&velocity), "velocity {:?}", velocity);
+        let rpm = encoder.rpm().unwrap();
+        assert!((-500.0..0.0).contains(&rpm), "rpm {:?}", rpm);
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(0.0, encoder.velocity());
+    }
+
+    #[test]
+    fn polled_inputs_test() {
+        let a = Mock::new(&[
+            Transaction::get(State::Low),
+            Transaction::get(State::High),
+            Transaction::get(State::High),
+        ]);
+        let b = Mock::new(&[
+            Transaction::get(State::Low),
+            Transaction::get(State::Low),
+            Transaction::get(State::High),
+        ]);
+        let mut encoder = Encoder::from_inputs(HalInput::new(a.clone()), HalInput::new(b.clone())).unwrap();
+        assert!(!encoder.is_interrupt_driven());
+        encoder.poll().unwrap();
+        encoder.poll().unwrap();
+        assert_eq!(2, encoder.count());
+
+        a.clone().done();
+        b.clone().done();
+    }
+}
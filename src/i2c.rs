@@ -0,0 +1,260 @@
+use crate::backend::BackendError;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, NoAcknowledgeSource, Operation};
+use motor_shield_core::pca9685::{self, MODE1, MODE1_AI, MODE1_RESTART, MODE1_SLEEP, PRESCALE};
+use rppal::i2c::I2c as RppalBus;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// An I2C bus of the Raspberry Pi, driven by rppal.
+pub struct RppalI2c {
+    /// The rppal bus.
+    bus: RppalBus,
+}
+
+impl RppalI2c {
+    /// Opens the default I2C bus, which is bus 1 on the header pins 3 and 5.
+    pub fn new() -> Result<RppalI2c, BackendError> {
+        return Ok(RppalI2c { bus: RppalBus::new()? });
+    }
+
+    /// Opens the I2C bus with the specified number.
+    pub fn with_bus(bus: u8) -> Result<RppalI2c, BackendError> {
+        return Ok(RppalI2c { bus: RppalBus::with_bus(bus)? });
+    }
+}
+
+impl i2c::ErrorType for RppalI2c {
+    type Error = BackendError;
+}
+
+impl i2c::I2c for RppalI2c {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), BackendError> {
+        self.bus.set_slave_address(address as u16)?;
+        let mut i = 0;
+        while i < operations.len() {
+            // a write that is followed by a read uses a repeated start, so
+            // the register pointer of the device is kept for the read
+            if let [Operation::Write(w), Operation::Read(r), ..] = &mut operations[i..] {
+                self.bus.write_read(w, r)?;
+                i += 2;
+                continue;
+            }
+
+            match &mut operations[i] {
+                Operation::Write(w) => { self.bus.write(w)?; },
+                Operation::Read(r) => { self.bus.read(r)?; },
+            };
+            i += 1;
+        }
+
+        return Ok(());
+    }
+}
+
+/// A delay that sleeps the current thread, for drivers that have to wait
+/// on the chip, like the PCA9685 when it wakes up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
+
+/// A PCA9685 that only exists in memory. It keeps all registers of the chip
+/// and follows the register pointer, auto increment and the sleep bit that
+/// guards the prescaler like the real chip does. Clones share the same
+/// registers, so a clone can be handed to a driver while the original is
+/// used to inspect the registers.
+#[derive(Debug, Clone)]
+pub struct MemoryPca9685 {
+    /// The address that the chip responds to.
+    address: u8,
+    /// The registers and every register write.
+    state: Arc<Mutex<Pca9685State>>,
+}
+
+#[derive(Debug)]
+struct Pca9685State {
+    /// All 256 registers of the chip.
+    registers: [u8; 256],
+    /// The register that the next byte is written to or read from.
+    pointer: u8,
+    /// Every register write in the order in which it was received.
+    writes: Vec<(u8, u8)>,
+}
+
+impl MemoryPca9685 {
+    /// Creates a new chip on the specified address with the registers in
+    /// their power on state: asleep, the prescaler at 200 Hz and all channels
+    /// fully off.
+    pub fn new(address: u8) -> MemoryPca9685 {
+        let mut registers = [0; 256];
+        registers[MODE1 as usize] = MODE1_SLEEP | 0x01;
+        registers[pca9685::MODE2 as usize] = pca9685::MODE2_OUTDRV;
+        registers[PRESCALE as usize] = 0x1E;
+        for channel in 0..pca9685::CHANNELS {
+            registers[MemoryPca9685::channel_register(channel) + 3] = 0x10;
+        }
+
+        return MemoryPca9685 {
+            address,
+            state: Arc::new(Mutex::new(Pca9685State { registers, pointer: 0, writes: Vec::new() })),
+        };
+    }
+
+    /// Returns the address that the chip responds to.
+    pub fn address(&self) -> u8 {
+        return self.address;
+    }
+
+    /// Returns the value of the specified register.
+    pub fn register(&self, register: u8) -> u8 {
+        return self.state.lock().unwrap().registers[register as usize];
+    }
+
+    /// Returns the on and off counter values of the specified channel,
+    /// including the full on and full off bits.
+    pub fn channel(&self, channel: u8) -> (u16, u16) {
+        let state = self.state.lock().unwrap();
+        let r = MemoryPca9685::channel_register(channel);
+        let on = u16::from_le_bytes([state.registers[r], state.registers[r + 1]]);
+        let off = u16::from_le_bytes([state.registers[r + 2], state.registers[r + 3]]);
+        return (on & 0x1FFF, off & 0x1FFF);
+    }
+
+    /// Returns the duty cycle of the specified channel out of
+    /// `RESOLUTION`. Full off wins over full on, like on the real chip.
+    pub fn duty(&self, channel: u8) -> u16 {
+        let (on, off) = self.channel(channel);
+        if off & pca9685::RESOLUTION != 0 {
+            return 0;
+        }
+
+        if on & pca9685::RESOLUTION != 0 {
+            return pca9685::RESOLUTION;
+        }
+
+        return off.wrapping_sub(on) % pca9685::RESOLUTION;
+    }
+
+    /// Returns whether the specified channel is fully on.
+    pub fn is_high(&self, channel: u8) -> bool {
+        return self.duty(channel) == pca9685::RESOLUTION;
+    }
+
+    /// Returns every register write as a register and value pair.
+    pub fn writes(&self) -> Vec<(u8, u8)> {
+        return self.state.lock().unwrap().writes.clone();
+    }
+
+    /// Clears the recorded register writes. The registers are kept.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().writes.clear();
+    }
+
+    /// Returns the index of the first register of a channel.
+    fn channel_register(channel: u8) -> usize {
+        return pca9685::LED0_ON_L as usize + 4 * channel as usize;
+    }
+}
+
+impl Pca9685State {
+    /// Moves the register pointer to the next register if auto increment is
+    /// enabled.
+    fn advance(&mut self) {
+        if self.registers[MODE1 as usize] & MODE1_AI != 0 {
+            self.pointer = self.pointer.wrapping_add(1);
+        }
+    }
+
+    /// Writes a byte to the register that the pointer is on.
+    fn write(&mut self, value: u8) {
+        let register = self.pointer;
+        self.writes.push((register, value));
+        let sleeping = self.registers[MODE1 as usize] & MODE1_SLEEP != 0;
+        match register {
+            // the prescaler can only be written while the oscillator sleeps
+            PRESCALE if !sleeping => (),
+            // writing a one to the restart bit clears it
+            MODE1 => self.registers[MODE1 as usize] = value & !MODE1_RESTART,
+            _ => self.registers[register as usize] = value,
+        };
+        self.advance();
+    }
+}
+
+impl i2c::ErrorType for MemoryPca9685 {
+    type Error = BackendError;
+}
+
+impl i2c::I2c for MemoryPca9685 {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), BackendError> {
+        if address != self.address {
+            return Err(BackendError::Bus(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for operation in operations.iter_mut() {
+            match operation {
+                Operation::Write(bytes) => {
+                    // the first byte of a write moves the register pointer
+                    if let Some((register, values)) = bytes.split_first() {
+                        state.pointer = *register;
+                        for value in values {
+                            state.write(*value);
+                        }
+                    }
+                },
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = state.registers[state.pointer as usize];
+                        state.advance();
+                    }
+                },
+            };
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::I2c;
+    use motor_shield_core::pca9685::Pca9685;
+
+    #[test]
+    fn memory_pca9685_registers_test() {
+        let chip = MemoryPca9685::new(0x40);
+        let mut pca = Pca9685::new(chip.clone(), 0x40);
+        assert_eq!(0, chip.duty(0));
+
+        pca.init().unwrap();
+        pca.set_frequency(50, &mut StdDelay).unwrap();
+        assert_eq!(121, chip.register(PRESCALE));
+        assert_eq!(MODE1_AI, chip.register(MODE1));
+
+        pca.set_duty(3, 1024).unwrap();
+        pca.set_pin(4, true).unwrap();
+        assert_eq!((0, 1024), chip.channel(3));
+        assert_eq!(1024, chip.duty(3));
+        assert!(chip.is_high(4));
+    }
+
+    #[test]
+    fn memory_pca9685_prescale_needs_sleep_test() {
+        let mut chip = MemoryPca9685::new(0x40);
+        chip.write(0x40, &[MODE1, MODE1_AI]).unwrap();
+        chip.write(0x40, &[PRESCALE, 3]).unwrap();
+        assert_eq!(0x1E, chip.register(PRESCALE));
+        assert_eq!(vec![(MODE1, MODE1_AI), (PRESCALE, 3)], chip.writes());
+
+        let mut value = [0];
+        assert!(chip.write_read(0x41, &[PRESCALE], &mut value).is_err());
+    }
+}
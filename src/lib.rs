@@ -1,3 +1,23 @@
+#![allow(clippy::needless_return)]
+
+pub mod backend;
 pub mod motor;
 pub mod board;
+pub mod drive;
+pub mod encoder;
+pub mod i2c;
+pub mod limit;
+pub mod planner;
+pub mod ramp;
+pub mod run;
 pub mod servo;
+pub mod servo_driver;
+pub mod shared;
+pub mod shield_v2;
+pub mod speed;
+pub mod stack;
+pub mod stepper;
+pub mod sysfs;
+pub mod wiring;
+
+pub use motor_shield_core::shift_register;
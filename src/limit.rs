@@ -0,0 +1,126 @@
+use crate::backend::{BackendError, EdgeInput, InputBackend, RppalBackend};
+use crate::board::BoardType;
+use motor_shield_core::debounce::Debouncer;
+use core::time::Duration;
+use std::time::Instant;
+
+/// # A limit switch
+///
+/// A limit switch marks the end of the travel of an axis. It is read on an
+/// input pin with the pull up enabled, so a switch that connects the pin to
+/// ground is pressed while the pin is low. The switch is polled, and its
+/// contacts are debounced: a change is only seen once the pin has kept its
+/// new level for the debounce time.
+pub struct LimitSwitch {
+    /// the input pin of the switch
+    input: Box<dyn EdgeInput + Send>,
+    /// the debouncer of the level of the pin
+    debouncer: Debouncer,
+    /// whether the switch is pressed while the pin is low
+    active_low: bool,
+    /// the moment that the times of the debouncer are relative to
+    epoch: Instant,
+}
+
+impl LimitSwitch {
+    /// The debounce time that is used until it is set.
+    pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(5);
+
+    /// Creates a new limit switch on the specified pin using rppal. The pin
+    /// is interpreted using the board type.
+    pub fn new(t: BoardType, pin: u8) -> Result<LimitSwitch, BackendError> {
+        return LimitSwitch::with_backend(t, &RppalBackend, pin);
+    }
+
+    /// Creates a new limit switch on the specified pin of the backend.
+    pub fn with_backend<I: InputBackend>(t: BoardType, backend: &I, pin: u8) -> Result<LimitSwitch, BackendError>
+    where I::Input: Send + 'static {
+        println!("creating limit switch on pin {:?}", pin);
+        return LimitSwitch::from_input(backend.input(t.to_bcm(pin)?)?);
+    }
+
+    /// Creates a new limit switch on an input pin that was created outside
+    /// of a backend, for example a mock.
+    pub fn from_input<P: EdgeInput + Send + 'static>(input: P) -> Result<LimitSwitch, BackendError> {
+        let mut input = input;
+        let level = input.is_high()?;
+        return Ok(LimitSwitch {
+            input: Box::new(input),
+            debouncer: Debouncer::new(level, Self::DEFAULT_DEBOUNCE),
+            active_low: true,
+            epoch: Instant::now(),
+        });
+    }
+
+    /// Sets whether the switch is pressed while the pin is low, which is the
+    /// default, or while it is high.
+    pub fn set_active_low(&mut self, active_low: bool) {
+        self.active_low = active_low;
+    }
+
+    /// Sets the time that the pin has to keep a new level before the change
+    /// is seen.
+    pub fn set_debounce(&mut self, time: Duration) {
+        self.debouncer.set_time(time);
+    }
+
+    /// Returns the time that the pin has to keep a new level before the
+    /// change is seen.
+    pub fn get_debounce(&self) -> Duration {
+        return self.debouncer.time();
+    }
+
+    /// Reads the pin and returns whether the switch is pressed, after
+    /// debouncing.
+    pub fn is_pressed(&mut self) -> Result<bool, BackendError> {
+        let level = self.input.is_high()?;
+        let high = self.debouncer.update(level, self.epoch.elapsed());
+        return Ok(high != self.active_low);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{HalInput, MemoryBackend};
+    use embedded_hal_mock::eh1::digital::{Mock, State, Transaction};
+    use std::thread;
+
+    #[test]
+    fn debounced_press_test() {
+        let backend = MemoryBackend::new();
+        backend.set_input(17, true);
+        let mut switch = LimitSwitch::with_backend(BoardType::BCM, &backend, 17).unwrap();
+        switch.set_debounce(Duration::from_millis(10));
+        assert!(!switch.is_pressed().unwrap());
+
+        // the press is only seen once the pin stays low
+        backend.set_input(17, false);
+        assert!(!switch.is_pressed().unwrap());
+        backend.set_input(17, true);
+        assert!(!switch.is_pressed().unwrap());
+        backend.set_input(17, false);
+        assert!(!switch.is_pressed().unwrap());
+        thread::sleep(Duration::from_millis(15));
+        assert!(switch.is_pressed().unwrap());
+
+        switch.set_active_low(false);
+        assert!(!switch.is_pressed().unwrap());
+    }
+
+    #[test]
+    fn mocked_input_test() {
+        let pin = Mock::new(&[
+            Transaction::get(State::High),
+            Transaction::get(State::Low),
+            Transaction::get(State::Low),
+        ]);
+        let mut switch = LimitSwitch::from_input(HalInput::new(pin.clone())).unwrap();
+        switch.set_debounce(Duration::ZERO);
+        assert_eq!(Duration::ZERO, switch.get_debounce());
+        assert!(switch.is_pressed().unwrap());
+        assert!(switch.is_pressed().unwrap());
+
+        pin.clone().done();
+    }
+}
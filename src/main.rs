@@ -7,14 +7,12 @@ use std::time::Duration;
 fn main() {
     println!("main started");
 
-    let low = Duration::from_micros(500);
-    let high = Duration::from_micros(2500);
     let mut servo = Servo::new_default(26);
-    servo.set_angle(0);
+    servo.set_angle(0).unwrap();
     sleep(Duration::from_secs(4));
-    servo.set_angle(90);
+    servo.set_angle(90).unwrap();
     sleep(Duration::from_secs(4));
-    servo.set_angle(180);
+    servo.set_angle(180).unwrap();
     sleep(Duration::from_secs(4));
     
 //    start_motor_full(1);
@@ -31,6 +29,7 @@ fn main() {
 }
 
 
+#[allow(dead_code)]
 fn start_motor(n: usize) {
     let mut board = AMSBoard::new(BoardType::BCM);
     let mut motor = Motor::new();
@@ -40,27 +39,28 @@ fn start_motor(n: usize) {
     let low = Duration::from_millis(2);
     let step = Duration::from_millis(2);
 
-    motor.set_pin(21);
+    motor.set_pin(21).unwrap();
     board.set_shift_register_pins(16, 20, 19);
 
-    board.set_motor(motor, n);
+    board.set_motor(motor, n).unwrap();
     board.test_motor_range(n, cycle, low, up, step);
 }
 
+#[allow(dead_code)]
 fn start_motor_full(n: usize) {
     let mut board = AMSBoard::new(BoardType::BCM);
     let mut motor = Motor::new();
 
-    motor.set_pin(21);
+    motor.set_pin(21).unwrap();
     board.set_shift_register_pins(16, 20, 19);
 
-    board.set_motor(motor, n);
-    board.start_motor_full(n);
+    board.set_motor(motor, n).unwrap();
+    board.start_motor_full(n).unwrap();
     sleep(Duration::from_secs(2));
-    board.stop_motor(n);
+    board.stop_motor(n).unwrap();
 
     board.invert_motor_direction(n);
-    board.start_motor_full(n);
+    board.start_motor_full(n).unwrap();
     sleep(Duration::from_secs(2));
-    board.stop_motor(n);
+    board.stop_motor(n).unwrap();
 }
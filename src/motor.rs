@@ -1,11 +1,30 @@
-use rppal::gpio::{OutputPin, Gpio, Error as GpioError};
+use crate::backend::{self, GpioBackend, BackendError, PwmOutput, RppalBackend};
+use crate::board::BoardType;
+use crate::sysfs::PwmChip;
+use embedded_hal::pwm::SetDutyCycle;
 use core::time::Duration;
 use std::thread::sleep;
 
+pub use motor_shield_core::motor::{Direction, MotorConfig, StopMode};
+pub use motor_shield_core::directions::SlotState;
+
 /// The motor structure.
-pub struct Motor {
-    /// The pin of the motor.
-    pub pin: Option<OutputPin>, 
+pub struct Motor<B: GpioBackend = RppalBackend> {
+    /// The backend that the pin of the motor is claimed from.
+    backend: B,
+    /// The pwm output of the motor.
+    pub pin: Option<PwmOutput<B::Pwm>>,
+    /// The numbering of the pin number that is passed to `set_pin`.
+    board_type: BoardType,
+    /// The BCM number of the pin, if it was claimed from the backend.
+    pin_number: Option<u8>,
+    /// The period of the pwm signal of a pin that is claimed from the
+    /// backend.
+    period: Duration,
+    /// The signed speed that the motor was last started with.
+    speed: f32,
+    /// The way in which the motor was stopped, until it is started again.
+    stop_mode: Option<StopMode>,
     /// The direction of the motor, as an enum.
     direction: Direction,
     /// The status of the motor, for logging purposes.
@@ -13,11 +32,34 @@ pub struct Motor {
 }
 
 impl Motor {
-    /// Creates a new motor without a pin set.
+    /// Creates a new motor without a pin set, using the rppal backend.
     pub fn new() -> Motor {
+        return Motor::with_backend(RppalBackend);
+    }
+}
+
+impl Default for Motor {
+    fn default() -> Motor {
+        return Motor::new();
+    }
+}
+
+impl<B: GpioBackend> Motor<B> {
+    /// The period of the pwm signal of a motor pin that is claimed by number.
+    pub const PWM_PERIOD: Duration = Duration::from_millis(20);
+
+    /// Creates a new motor without a pin set. The pin will be claimed from
+    /// the specified backend.
+    pub fn with_backend(backend: B) -> Motor<B> {
         println!("created new motor");
         return Motor {
+            backend,
             pin: None,
+            board_type: BoardType::BCM,
+            pin_number: None,
+            period: Self::PWM_PERIOD,
+            speed: 0.0,
+            stop_mode: None,
             direction: Direction::Clockwise,
             status: Status::Idle,
         }
@@ -26,14 +68,16 @@ impl Motor {
     /// Tests a range of a motor using pulse width and cycle.
     pub fn test_range(&mut self, cycle: Duration, low: Duration, up: Duration, step: Duration) {
         println!("testing motor pwm range");
-        let output_pin: &mut OutputPin = match self.pin.as_mut() {
+        let output_pin: &mut PwmOutput<B::Pwm> = match self.pin.as_mut() {
             Some(g) => g,
             None => panic!("pin for this motor is not set, can not test range"),
         };
         let mut cur = low;
         while cur < up {
             println!("cycle={:?} width={:?}", cycle, cur);
-            output_pin.set_pwm(cycle, cur);
+            if let Err(e) = backend::set_pulse_width(output_pin, cycle, cur) {
+                panic!("{:?}", e);
+            }
             sleep(Duration::from_millis(500));
             cur += step;
         }
@@ -48,6 +92,7 @@ impl Motor {
             Status::PWM => return true,
             Status::Idle => return false,
             Status::Running => return true,
+            Status::Braking => return false,
         }
     }
 
@@ -65,33 +110,120 @@ impl Motor {
     pub fn set_direction(&mut self, d: Direction) {
         println!("setting direction to {:?}", d);
         self.direction = d;
+        self.speed = match d {
+            Direction::Clockwise => self.speed.abs(),
+            Direction::Counterclockwise => -self.speed.abs(),
+        };
     }
 
-    /// Sets the pin of this motor.
-    pub fn set_pin(&mut self, p: u8) -> Result<(), GpioError> {
-        println!("setting motor pin to: {:?}", p);
-        let gpio = Gpio::new();
-        let gpio_pin = match gpio {
-            Ok(gp) => gp,
-            Err(e) => panic!("{:?}", e),
-        };
+    /// Sets the numbering of the pin number that is passed to `set_pin`. A
+    /// motor uses `BoardType::BCM` until it is set on a board, after which it
+    /// uses the numbering of the board.
+    pub fn set_board_type(&mut self, t: BoardType) {
+        self.board_type = t;
+    }
 
-        match gpio_pin.get(p) {
-            Ok(pin) => self.pin = Some(pin.into_output()),
-            Err(e) => panic!("{:?}", e),
-        };
+    /// Returns the numbering of the pin number that is passed to `set_pin`.
+    pub fn get_board_type(&self) -> BoardType {
+        return self.board_type;
+    }
 
+    /// Sets the pin of this motor. The pin is claimed from the backend as a
+    /// pwm pin with the period of the pwm frequency of the motor, which is
+    /// `PWM_PERIOD` by default. The pin number is interpreted using the board
+    /// type of the motor.
+    pub fn set_pin(&mut self, p: u8) -> Result<(), BackendError> {
+        println!("setting motor pin to: {:?}", p);
+        let bcm = self.board_type.to_bcm(p)?;
+        self.pin = None; // release the previous pin before claiming
+        self.pin = Some(PwmOutput::Pin(self.backend.pwm(bcm, self.period)?));
+        self.pin_number = Some(bcm);
         println!("pin is set");
 
         return Ok(());
     }
 
+    /// Sets a pwm pin that was created outside of the backend as the pin of
+    /// this motor.
+    pub fn set_pwm_pin(&mut self, pin: B::Pwm) {
+        println!("setting motor pwm pin");
+        self.pin = Some(PwmOutput::Pin(pin));
+        self.pin_number = None;
+    }
+
+    /// Binds this motor to a channel of a hardware pwm chip instead of a pin
+    /// of the backend. The channel runs with the period of the pwm frequency
+    /// of the motor.
+    pub fn set_pwm_channel(&mut self, chip: &PwmChip, channel: u8) -> Result<(), BackendError> {
+        println!("setting motor pwm channel to: {:?}", channel);
+        self.pin = None; // release the previous channel before claiming
+        self.pin = Some(PwmOutput::Channel(chip.channel(channel, self.period)?));
+        self.pin_number = None;
+        return Ok(());
+    }
+
+    /// Sets the frequency of the pwm signal of this motor in Hz. A pin that
+    /// was claimed from the backend is claimed again with the new period and
+    /// the motor is stopped. The frequency of a pin or channel that was set
+    /// from outside of the backend can not be changed, it applies to the next
+    /// pwm channel that is set.
+    pub fn set_pwm_frequency(&mut self, hz: u32) -> Result<(), MotorError> {
+        if hz == 0 || hz > 1_000_000 {
+            return Err(MotorError::InvalidFrequency);
+        }
+
+        println!("setting motor pwm frequency to {:?} hz", hz);
+        self.period = Duration::from_nanos(1_000_000_000 / hz as u64);
+        if let Some(p) = self.pin_number {
+            self.pin = None;
+            self.pin = Some(PwmOutput::Pin(self.backend.pwm(p, self.period).map_err(MotorError::Backend)?));
+            self.status = Status::Idle;
+            self.speed = 0.0;
+        } else if self.pin.is_some() {
+            return Err(MotorError::Backend(BackendError::Unsupported));
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the period of the pwm signal of this motor.
+    pub fn get_pwm_period(&self) -> Duration {
+        return self.period;
+    }
+
+    /// Returns the signed speed that this motor is running at, from -1.0 to
+    /// 1.0. A negative speed runs counterclockwise.
+    pub fn get_speed(&self) -> f32 {
+        return self.speed;
+    }
+
     /// Returns the directions of this motor.
     pub fn get_direction(&self) -> Direction {
         println!("retrieving direction");
         return self.direction;
     }
 
+    /// Returns the state of the L293D inputs of this motor: its direction,
+    /// or the way in which it was stopped until it is started again.
+    pub fn get_state(&self) -> SlotState {
+        return match self.stop_mode {
+            Some(m) => SlotState::Stopped(m),
+            None => SlotState::Run(self.direction),
+        };
+    }
+
+    /// Returns the way in which this motor was stopped, or `None` if it was
+    /// not stopped with a stop mode since it was last started.
+    pub fn get_stop_mode(&self) -> Option<StopMode> {
+        return self.stop_mode;
+    }
+
+    /// Sets the way in which this motor is stopped without touching its pin.
+    /// With `None` the direction is used for the L293D inputs again.
+    pub(crate) fn set_stop_mode(&mut self, mode: Option<StopMode>) {
+        self.stop_mode = mode;
+    }
+
     /// Returns the status of this board.
     pub fn get_status(&self) -> Status {
         println!("getting status");
@@ -112,21 +244,48 @@ impl Motor {
             return Err(MotorError::PinNotSet);
         }
 
-        let pin = self.pin.as_mut().unwrap();
+        if !mc.is_full() && mc.width() > mc.cycle() {
+            return Err(MotorError::PWMDurationTooHigh);
+        }
 
         println!("starting motor");
 
-        if mc.full {
+        if mc.is_full() {
             println!("full speed");
-            pin.set_high();
-            println!("pin is set to high: {:?}", pin.is_set_high());
+        } else {
+            println!("pwm cycle: {:?} width: {:?}", mc.cycle(), mc.width());
+        }
+
+        return self.set_duty(mc);
+    }
+
+    /// Sets the duty cycle of the pin from the configuration without
+    /// logging, for control loops that change it on every update. The
+    /// direction is kept.
+    pub(crate) fn set_duty(&mut self, mc: MotorConfig) -> Result<(), MotorError> {
+        if !mc.is_full() && mc.width() > mc.cycle() {
+            return Err(MotorError::PWMDurationTooHigh);
+        }
+
+        let pin = match self.pin.as_mut() {
+            Some(p) => p,
+            None => return Err(MotorError::PinNotSet),
+        };
+        let sign = match self.direction {
+            Direction::Clockwise => 1.0,
+            Direction::Counterclockwise => -1.0,
+        };
+
+        if mc.is_full() {
+            pin.set_duty_cycle_fully_on().map_err(MotorError::Backend)?;
             self.status = Status::Running;
+            self.speed = sign;
             return Ok(());
         }
 
-        println!("pwm cycle: {:?} width: {:?}", mc.cycle, mc.width);
-        pin.set_pwm(mc.cycle, mc.width);
+        backend::set_pulse_width(pin, mc.cycle(), mc.width()).map_err(MotorError::Backend)?;
         self.status = Status::PWM;
+        self.speed = sign * mc.duty();
 
         return Ok(());
     }
@@ -139,46 +298,40 @@ impl Motor {
 
         println!("stopping motor");
 
-        let pin: &mut OutputPin = self.pin.as_mut().unwrap();
-        pin.set_low();
+        let pin: &mut PwmOutput<B::Pwm> = self.pin.as_mut().unwrap();
+        pin.set_duty_cycle_fully_off().map_err(MotorError::Backend)?;
         self.status = Status::Idle;
+        self.speed = 0.0;
 
         return Ok(());
     }
-}
-
-#[derive(Debug)]
-pub struct MotorConfig {
-    cycle: Duration,
-    width: Duration,
-    full: bool
-}
 
-/// Motor configuration structure.
-impl MotorConfig {
-    /// Returns a new config with the cycle and width set.
-    pub fn new_pwm(c: Duration, w: Duration) -> MotorConfig {
-        return MotorConfig {
-            cycle: c,
-            width: w,
-            full: false,
+    /// Stops this motor in the specified way. Braking turns the enable fully
+    /// on, since the L293D only brakes while it is enabled; both inputs have
+    /// to be high for this, which is done by the board. Releasing turns the
+    /// enable off, so the motor coasts to a stop.
+    pub fn stop_with(&mut self, mode: StopMode) -> Result<(), MotorError> {
+        if self.pin.is_none() {
+            return Err(MotorError::PinNotSet);
         }
-    }
 
-    /// Rerturns a new config with the full property set to true.
-    pub fn new_full() -> MotorConfig {
-        return MotorConfig {
-            cycle: Duration::from_millis(100),
-            width: Duration::from_millis(100),
-            full: true,
-        }
-    }
-}
+        println!("stopping motor with {:?}", mode);
+        let pin: &mut PwmOutput<B::Pwm> = self.pin.as_mut().unwrap();
+        match mode {
+            StopMode::Brake => {
+                pin.set_duty_cycle_fully_on().map_err(MotorError::Backend)?;
+                self.status = Status::Braking;
+            },
+            StopMode::Release => {
+                pin.set_duty_cycle_fully_off().map_err(MotorError::Backend)?;
+                self.status = Status::Idle;
+            },
+        };
+        self.stop_mode = Some(mode);
+        self.speed = 0.0;
 
-#[derive(Debug, Clone, Copy)]
-pub enum Direction {
-    Clockwise,
-    Counterclockwise
+        return Ok(());
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -186,6 +339,7 @@ pub enum Status {
     PWM,
     Idle,
     Running,
+    Braking,
 }
 
 #[derive(Debug)]
@@ -193,6 +347,15 @@ pub enum MotorError {
     PinNotSet,
     SpeedIsZero,
     MotorNotFound,
+    EncoderNotFound,
     MotorIndexOutOfBounds,
     PWMDurationTooHigh,
+    InvalidSpeed,
+    InvalidFrequency,
+    DuplicateSlot,
+    InvalidStepsPerRevolution,
+    InvalidStepMode,
+    LimitNotFound,
+    PositionOutOfBounds,
+    Backend(BackendError),
 }
@@ -0,0 +1,446 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::{AMSBoard, BoardError};
+use crate::motor::MotorError;
+use crate::stepper::Stepper;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+pub use motor_shield_core::planner::Planner;
+
+/// # A stepper motor with acceleration
+///
+/// A stepper that is stepped at a fixed interval stalls when the interval
+/// is too short to start from standstill. The planned stepper moves the
+/// stepper in a background thread with a trapezoidal profile: it accelerates
+/// to the maximum speed, cruises, and decelerates to stand still on the
+/// target, like AccelStepper on the Arduino. The target can be changed while
+/// the motor moves, and a motor that has to turn around decelerates first.
+/// Positions are in steps of the mode of the stepper and speeds in steps per
+/// second. The soft limits of the stepper are taken over and checked for
+/// every target, and they are given back with the stepper. Dropping the
+/// planned stepper ends the thread and releases the coils.
+pub struct PlannedStepper<B: GpioBackend = RppalBackend> {
+    /// the planner that is shared with the thread
+    motion: Arc<(Mutex<Motion>, Condvar)>,
+    /// the phase units in one step of the mode of the stepper
+    stride: i64,
+    /// the thread that makes the steps, which returns the stepper, until it
+    /// is ended
+    thread: Option<JoinHandle<Result<Stepper<B>, BoardError>>>,
+}
+
+/// The state of the motion that is shared with the thread.
+struct Motion {
+    /// the planner of the steps
+    planner: Planner,
+    /// the soft limits of the stepper, in phase units
+    soft_limits: Option<(i64, i64)>,
+    /// whether the thread is driving the coils for a step that the planner
+    /// has already counted
+    stepping: bool,
+    /// whether the thread should end
+    shutdown: bool,
+    /// whether the thread has ended
+    finished: bool,
+}
+
+impl<B: GpioBackend + 'static> PlannedStepper<B> where AMSBoard<B>: Send {
+    /// Starts moving the stepper in the background with the specified maximum
+    /// speed in steps per second and acceleration in steps per second
+    /// squared. The motor starts standing still at the position of the
+    /// stepper.
+    pub fn new(stepper: Stepper<B>, max_speed: f32, acceleration: f32) -> Result<PlannedStepper<B>, BoardError> {
+        let mut planner = match Planner::new(max_speed, acceleration) {
+            Some(p) => p,
+            None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+        };
+        planner.set_position(stepper.get_position());
+        let stride = stepper.get_mode().stride();
+
+        println!("planning stepper at {:?} steps/s and {:?} steps/s²", max_speed, acceleration);
+        let motion = Arc::new((Mutex::new(Motion {
+            planner,
+            soft_limits: stepper.phase_soft_limits(),
+            stepping: false,
+            shutdown: false,
+            finished: false,
+        }), Condvar::new()));
+        let shared = Arc::clone(&motion);
+
+        let thread = thread::spawn(move || {
+            let mut stepper = stepper;
+            stepper.align_phase();
+            let (lock, condvar) = &*shared;
+            let mut last: Option<Instant> = None;
+            let mut m = lock.lock().unwrap();
+            loop {
+                if m.shutdown {
+                    m.finished = true;
+                    stepper.set_phase_soft_limits(m.soft_limits);
+                    return Ok(stepper);
+                }
+
+                let interval = match m.planner.step_interval() {
+                    Some(i) => i,
+                    None => {
+                        // standing still on the target until it changes
+                        last = None;
+                        m = condvar.wait(m).unwrap();
+                        continue;
+                    },
+                };
+
+                // the target may change while waiting for the next step
+                let now = Instant::now();
+                let due = match last {
+                    Some(l) => l + interval,
+                    None => now,
+                };
+                if due > now {
+                    m = condvar.wait_timeout(m, due - now).unwrap().0;
+                    continue;
+                }
+
+                // the board is driven without holding the motion, so the
+                // position and the target can be used during the step
+                let direction = m.planner.step();
+                m.stepping = true;
+                drop(m);
+                let result = stepper.single_step(direction);
+                m = lock.lock().unwrap();
+                m.stepping = false;
+                if let Err(e) = result {
+                    m.finished = true;
+                    condvar.notify_all();
+                    return Err(e);
+                }
+                last = Some(now);
+
+                if !m.planner.is_running() {
+                    println!("stepper has reached position {:?}", m.planner.position());
+                    condvar.notify_all();
+                }
+            }
+        });
+
+        return Ok(PlannedStepper { motion, stride, thread: Some(thread) });
+    }
+
+    /// Sets the maximum speed in steps per second. A motor that is faster
+    /// decelerates to the new speed.
+    pub fn set_max_speed(&self, max_speed: f32) -> Result<(), BoardError> {
+        return self.update(|p| p.set_max_speed(max_speed));
+    }
+
+    /// Returns the maximum speed in steps per second.
+    pub fn get_max_speed(&self) -> f32 {
+        return self.planner().max_speed();
+    }
+
+    /// Sets the acceleration and deceleration in steps per second squared.
+    pub fn set_acceleration(&self, acceleration: f32) -> Result<(), BoardError> {
+        return self.update(|p| p.set_acceleration(acceleration));
+    }
+
+    /// Returns the acceleration in steps per second squared.
+    pub fn get_acceleration(&self) -> f32 {
+        return self.planner().acceleration();
+    }
+
+    /// Moves the motor to an absolute position without blocking. Returns
+    /// `PositionOutOfBounds` and keeps the old target if the position is
+    /// outside of the soft limits.
+    pub fn move_to(&self, position: i64) -> Result<(), BoardError> {
+        let (lock, condvar) = &*self.motion;
+        let mut m = lock.lock().unwrap();
+        self.check_target(&m, position)?;
+        println!("moving stepper to {:?}", position);
+        m.planner.move_to(position);
+        condvar.notify_all();
+        return Ok(());
+    }
+
+    /// Moves the motor by a number of steps from its current position
+    /// without blocking, backward if the number is negative. See `move_to`.
+    pub fn move_by(&self, steps: i64) -> Result<(), BoardError> {
+        let (lock, condvar) = &*self.motion;
+        let mut m = lock.lock().unwrap();
+        self.check_target(&m, m.planner.position() + steps)?;
+        println!("moving stepper by {:?} steps", steps);
+        m.planner.move_by(steps);
+        condvar.notify_all();
+        return Ok(());
+    }
+
+    /// Sets the lowest and the highest position that the motor may move to,
+    /// which is checked for every target. Returns `PositionOutOfBounds` if
+    /// the lowest position is above the highest. A target that was set
+    /// before is not checked again.
+    pub fn set_soft_limits(&self, min: i64, max: i64) -> Result<(), BoardError> {
+        if min > max {
+            return Err(BoardError::MotorError(MotorError::PositionOutOfBounds));
+        }
+
+        println!("set soft limits of planned stepper to {:?}..={:?}", min, max);
+        self.motion.0.lock().unwrap().soft_limits = Some((min * self.stride, max * self.stride));
+        return Ok(());
+    }
+
+    /// Returns the lowest and the highest position that the motor may move
+    /// to, if they are set. Limits between two steps are rounded inward.
+    pub fn get_soft_limits(&self) -> Option<(i64, i64)> {
+        let stride = self.stride;
+        let soft_limits = self.motion.0.lock().unwrap().soft_limits;
+        return soft_limits.map(|(min, max)| (-(-min).div_euclid(stride), max.div_euclid(stride)));
+    }
+
+    /// Lets the motor move to any position.
+    pub fn clear_soft_limits(&self) {
+        self.motion.0.lock().unwrap().soft_limits = None;
+    }
+
+    /// Decelerates the motor to stand still as soon as the acceleration
+    /// allows. The target becomes the position that the motor stops on.
+    pub fn stop(&self) {
+        println!("stopping stepper");
+        self.update(|p| {
+            p.stop();
+            true
+        }).unwrap();
+    }
+
+    /// Returns the current position in steps.
+    pub fn get_position(&self) -> i64 {
+        return self.planner().position();
+    }
+
+    /// Returns the position that the motor moves to.
+    pub fn get_target(&self) -> i64 {
+        return self.planner().target();
+    }
+
+    /// Returns the number of steps from the current position to the target.
+    pub fn distance_to_go(&self) -> i64 {
+        return self.planner().distance_to_go();
+    }
+
+    /// Returns the current speed in steps per second, negative backward.
+    pub fn get_speed(&self) -> f32 {
+        return self.planner().speed();
+    }
+
+    /// Returns whether the motor is moving or has not reached the target.
+    pub fn is_running(&self) -> bool {
+        return self.planner().is_running();
+    }
+
+    /// Blocks until the motor stands still on the target. Also returns when
+    /// a step failed, the error is returned by `into_stepper`.
+    pub fn wait(&self) {
+        let (lock, condvar) = &*self.motion;
+        let mut m = lock.lock().unwrap();
+        while (m.planner.is_running() || m.stepping) && !m.finished {
+            m = condvar.wait(m).unwrap();
+        }
+    }
+
+    /// Ends the thread and returns the stepper, or the error of the board if
+    /// a step failed. A moving motor stops at once, without deceleration, so
+    /// `stop` and `wait` should be used first. The coils stay energized.
+    pub fn into_stepper(mut self) -> Result<Stepper<B>, BoardError> {
+        return match self.end_thread().unwrap() {
+            Ok(r) => r,
+            Err(e) => std::panic::resume_unwind(e),
+        };
+    }
+
+    /// Returns `PositionOutOfBounds` if the target is outside of the soft
+    /// limits of the motion.
+    fn check_target(&self, motion: &Motion, target: i64) -> Result<(), BoardError> {
+        if let Some((min, max)) = motion.soft_limits {
+            if !(min..=max).contains(&(target * self.stride)) {
+                return Err(BoardError::MotorError(MotorError::PositionOutOfBounds));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns a copy of the planner.
+    fn planner(&self) -> Planner {
+        return self.motion.0.lock().unwrap().planner;
+    }
+
+    /// Changes the planner and wakes the thread, so it plans the next step
+    /// again. Returns `InvalidSpeed` if the change is refused.
+    fn update<F: FnOnce(&mut Planner) -> bool>(&self, change: F) -> Result<(), BoardError> {
+        let (lock, condvar) = &*self.motion;
+        if !change(&mut lock.lock().unwrap().planner) {
+            return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+        }
+
+        condvar.notify_all();
+        return Ok(());
+    }
+}
+
+impl<B: GpioBackend> PlannedStepper<B> {
+    /// Tells the thread to end and waits for it. Returns `None` if the
+    /// thread has already been ended.
+    fn end_thread(&mut self) -> Option<thread::Result<Result<Stepper<B>, BoardError>>> {
+        let thread = self.thread.take()?;
+        let (lock, condvar) = &*self.motion;
+        lock.lock().unwrap_or_else(PoisonError::into_inner).shutdown = true;
+        condvar.notify_all();
+        return Some(thread.join());
+    }
+}
+
+impl<B: GpioBackend> Drop for PlannedStepper<B> {
+    fn drop(&mut self) {
+        if let Some(Ok(Ok(mut stepper))) = self.end_thread() {
+            if let Err(e) = stepper.release() {
+                println!("could not release stepper: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::board::BoardType;
+    use crate::motor::Motor;
+    use crate::shared::SharedBoard;
+    use crate::stepper::{StepMode, StepperPort};
+    use core::time::Duration;
+
+    fn planned_stepper(backend: &MemoryBackend, max_speed: f32, acceleration: f32) -> PlannedStepper<MemoryBackend> {
+        return PlannedStepper::new(stepper(backend), max_speed, acceleration).unwrap();
+    }
+
+    fn stepper(backend: &MemoryBackend) -> Stepper<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        board.set_shift_register_pins(16, 20, 19);
+        for (slot, pin) in [(1, 21), (2, 26)] {
+            let mut motor = Motor::with_backend(backend.clone());
+            motor.set_pin(pin).unwrap();
+            board.set_motor(motor, slot).unwrap();
+        }
+        let board = SharedBoard::new(board);
+        return Stepper::new(&board, StepperPort::M1M2, 200).unwrap();
+    }
+
+    /// Returns the number of direction bytes that were latched.
+    fn latches(backend: &MemoryBackend) -> usize {
+        return backend.actions(19).iter().filter(|a| **a == PinAction::High).count();
+    }
+
+    #[test]
+    fn move_to_test() {
+        let backend = MemoryBackend::new();
+        let planned = planned_stepper(&backend, 2000.0, 20000.0);
+        backend.clear();
+
+        // 100 steps to reach 2000 steps/s and 100 steps to stop again
+        let start = Instant::now();
+        planned.move_to(200).unwrap();
+        assert!(planned.is_running());
+        planned.wait();
+        assert!(start.elapsed() >= Duration::from_millis(150), "elapsed {:?}", start.elapsed());
+        assert_eq!(200, planned.get_position());
+        assert_eq!(0, planned.distance_to_go());
+        assert_eq!(0.0, planned.get_speed());
+        assert_eq!(200, latches(&backend));
+
+        planned.move_by(-50).unwrap();
+        planned.wait();
+        assert_eq!(150, planned.get_position());
+
+        let stepper = planned.into_stepper().unwrap();
+        assert_eq!(150, stepper.get_position());
+        assert!(stepper.is_energized());
+    }
+
+    #[test]
+    fn stop_decelerates_test() {
+        let backend = MemoryBackend::new();
+        let planned = planned_stepper(&backend, 1000.0, 4000.0);
+        planned.move_to(100_000).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && planned.get_position() < 10 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // the profile of the stop is tested with the planner of the core,
+        // here the motor only has to stop ahead of where it was stopped
+        let position = planned.get_position();
+        planned.stop();
+        let target = planned.get_target();
+        assert!(target >= position && target < 100_000, "stops at {:?}", target);
+        planned.wait();
+        assert_eq!(target, planned.get_position());
+        assert_eq!(0.0, planned.get_speed());
+        assert!(planned.into_stepper().is_ok());
+
+        let backend = MemoryBackend::new();
+        let planned = planned_stepper(&backend, 1000.0, 4000.0);
+        assert!(planned.set_acceleration(0.0).is_err());
+        assert!(planned.set_max_speed(500.0).is_ok());
+        assert_eq!(500.0, planned.get_max_speed());
+        assert_eq!(4000.0, planned.get_acceleration());
+    }
+
+    #[test]
+    fn drop_releases_test() {
+        let backend = MemoryBackend::new();
+        let planned = planned_stepper(&backend, 2000.0, 20000.0);
+        planned.move_to(10).unwrap();
+        planned.wait();
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+
+        // the thread ends and the coils are released
+        drop(planned);
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(21));
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(26));
+    }
+
+    #[test]
+    fn soft_limits_test() {
+        let backend = MemoryBackend::new();
+        let mut stepper = stepper(&backend);
+        stepper.set_soft_limits(-10, 10).unwrap();
+        let planned = PlannedStepper::new(stepper, 2000.0, 20000.0).unwrap();
+
+        assert!(matches!(planned.move_to(11), Err(BoardError::MotorError(MotorError::PositionOutOfBounds))));
+        assert_eq!(0, planned.get_target());
+        planned.move_by(-10).unwrap();
+        planned.wait();
+        assert!(planned.move_by(-1).is_err());
+        assert_eq!(-10, planned.get_position());
+
+        // the limits can change while planning and go back with the stepper
+        planned.set_soft_limits(-20, 0).unwrap();
+        assert!(planned.set_soft_limits(1, 0).is_err());
+        planned.move_to(-20).unwrap();
+        planned.wait();
+        assert!(planned.move_to(-21).is_err());
+        assert_eq!(Some((-20, 0)), planned.get_soft_limits());
+        let mut stepper = planned.into_stepper().unwrap();
+        assert_eq!(Some((-20, 0)), stepper.get_soft_limits());
+        stepper.set_mode(StepMode::Half).unwrap();
+        assert_eq!(Some((-40, 0)), stepper.get_soft_limits());
+
+        // in half step mode the limits and positions are in half steps
+        let planned = PlannedStepper::new(stepper, 2000.0, 20000.0).unwrap();
+        assert_eq!(Some((-40, 0)), planned.get_soft_limits());
+        assert_eq!(-40, planned.get_position());
+        assert!(planned.move_by(-1).is_err());
+        planned.clear_soft_limits();
+        assert!(planned.move_by(-1).is_ok());
+        planned.wait();
+        assert_eq!(None, planned.into_stepper().unwrap().get_soft_limits());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::{AMSBoard, BoardError};
+use crate::motor::MotorError;
+use crate::shared::{self, SharedBoard};
+use motor_shield_core::ramp;
+use core::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// # A board with acceleration ramps
+///
+/// Starting a motor at full speed or stopping it at once draws a current
+/// spike that can brown out the Raspberry Pi, and it is hard on the gears.
+/// The ramped board changes the speed of a motor in small steps in a
+/// background thread, with a configurable acceleration per motor slot. The
+/// shared board is only locked during a step.
+pub struct RampedBoard<B: GpioBackend = RppalBackend> {
+    /// the board that the ramps run on
+    board: SharedBoard<B>,
+    /// the acceleration of every slot, in duty cycle per second
+    acceleration: [f32; 4],
+    /// the time between two steps of a ramp
+    interval: Duration,
+}
+
+impl<B: GpioBackend + 'static> RampedBoard<B> where AMSBoard<B>: Send {
+    /// The acceleration that is used until it is set, which takes a motor
+    /// from standstill to full speed in half a second.
+    pub const DEFAULT_ACCELERATION: f32 = 2.0;
+    /// The time between two steps of a ramp that is used until it is set.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(20);
+
+    /// Creates a ramped board on a shared board, which can still be used
+    /// directly.
+    pub fn new(board: &SharedBoard<B>) -> RampedBoard<B> {
+        return RampedBoard {
+            board: board.clone(),
+            acceleration: [Self::DEFAULT_ACCELERATION; 4],
+            interval: Self::DEFAULT_INTERVAL,
+        };
+    }
+
+    /// Returns the shared board.
+    pub fn board(&self) -> SharedBoard<B> {
+        return self.board.clone();
+    }
+
+    /// Sets the acceleration of a motor slot in duty cycle per second. An
+    /// acceleration of 1.0 takes a motor from standstill to full speed in one
+    /// second.
+    pub fn set_acceleration(&mut self, n: usize, acceleration: f32) -> Result<(), BoardError> {
+        shared::check_slot(n)?;
+        if !acceleration.is_finite() || acceleration <= 0.0 {
+            return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+        }
+
+        self.acceleration[n - 1] = acceleration;
+        return Ok(());
+    }
+
+    /// Sets the time between two steps of a ramp.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Ramps a motor from its current speed to the target speed in the
+    /// background. When the target has the other sign, the motor is ramped
+    /// down to a stop first, then the direction is switched and the motor is
+    /// ramped up again. Other background work that is still running on the
+    /// same slot, like a ramp, is stopped.
+    pub fn ramp_to_speed(&self, n: usize, target: f32) -> Result<RampHandle, BoardError> {
+        shared::check_slot(n)?;
+        if !target.is_finite() || !(-1.0..=1.0).contains(&target) {
+            return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+        }
+
+        println!("ramping motor {:?} to speed {:?}", n, target);
+        let generation = self.board.claim(n)?;
+        let board = self.board.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancelled);
+        let max_step = self.acceleration[n - 1] * self.interval.as_secs_f32();
+        let interval = self.interval;
+
+        let thread = thread::spawn(move || {
+            let mut speed: Option<f32> = None;
+            loop {
+                {
+                    let mut board_lock = board.lock();
+                    // newer work on the same slot takes over
+                    if flag.load(Ordering::SeqCst) || !board.is_current(n, generation) {
+                        return Ok(());
+                    }
+
+                    let current = match speed {
+                        Some(s) => s,
+                        None => board_lock.get_motor_speed(n)?,
+                    };
+
+                    if current == target {
+                        return Ok(());
+                    }
+
+                    let next = ramp::step_towards(current, target, max_step);
+                    board_lock.set_motor_speed(n, next)?;
+                    speed = Some(next);
+                }
+
+                thread::sleep(interval);
+            }
+        });
+
+        return Ok(RampHandle { cancelled, thread });
+    }
+
+    /// Ramps a motor up from its current speed to the specified speed.
+    pub fn soft_start(&self, n: usize, speed: f32) -> Result<RampHandle, BoardError> {
+        return self.ramp_to_speed(n, speed);
+    }
+
+    /// Ramps a motor down to a stop.
+    pub fn soft_stop(&self, n: usize) -> Result<RampHandle, BoardError> {
+        return self.ramp_to_speed(n, 0.0);
+    }
+}
+
+/// The handle of a ramp that runs in the background.
+pub struct RampHandle {
+    /// whether the ramp should stop at its next step
+    cancelled: Arc<AtomicBool>,
+    /// the thread that runs the ramp
+    thread: JoinHandle<Result<(), BoardError>>,
+}
+
+impl RampHandle {
+    /// Stops the ramp at its next step. The motor keeps the speed of the last
+    /// step.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the ramp has finished.
+    pub fn is_finished(&self) -> bool {
+        return self.thread.is_finished();
+    }
+
+    /// Waits until the ramp has finished, and returns the error of the board
+    /// if a step failed.
+    pub fn wait(self) -> Result<(), BoardError> {
+        return match self.thread.join() {
+            Ok(r) => r,
+            Err(e) => std::panic::resume_unwind(e),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::board::BoardType;
+    use crate::motor::Motor;
+
+    fn ramped_board(backend: &MemoryBackend) -> RampedBoard<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 1).unwrap();
+        board.set_motor_frequency(1, 1000).unwrap();
+
+        let mut ramped = RampedBoard::new(&SharedBoard::new(board));
+        ramped.set_interval(Duration::from_millis(1));
+        ramped.set_acceleration(1, 250.0).unwrap();
+        return ramped;
+    }
+
+    fn pulse_widths(backend: &MemoryBackend) -> Vec<i64> {
+        return backend.actions(21).iter().map(|a| match a {
+            PinAction::Pwm { pulse_width, .. } => pulse_width.as_micros() as i64,
+            PinAction::High => 1000,
+            PinAction::Low => 0,
+        }).collect();
+    }
+
+    #[test]
+    fn soft_start_and_stop_test() {
+        let backend = MemoryBackend::new();
+        let ramped = ramped_board(&backend);
+        ramped.soft_start(1, 1.0).unwrap().wait().unwrap();
+        assert_eq!(vec![250, 500, 750, 1000], pulse_widths(&backend));
+
+        backend.clear();
+        ramped.soft_stop(1).unwrap().wait().unwrap();
+        assert_eq!(vec![750, 500, 250, 0], pulse_widths(&backend));
+        assert_eq!(0.0, ramped.board().lock().get_motor_speed(1).unwrap());
+    }
+
+    #[test]
+    fn ramp_crosses_zero_test() {
+        let backend = MemoryBackend::new();
+        let ramped = ramped_board(&backend);
+        ramped.ramp_to_speed(1, 0.5).unwrap().wait().unwrap();
+        assert_eq!(4, ramped.board().lock().get_directions());
+
+        backend.clear();
+        ramped.ramp_to_speed(1, -0.5).unwrap().wait().unwrap();
+        assert_eq!(vec![250, 0, 250, 500], pulse_widths(&backend));
+        assert_eq!(8, ramped.board().lock().get_directions());
+        assert_eq!(-0.5, ramped.board().lock().get_motor_speed(1).unwrap());
+    }
+
+    #[test]
+    fn newer_ramp_takes_over_test() {
+        let backend = MemoryBackend::new();
+        let mut ramped = ramped_board(&backend);
+        ramped.set_acceleration(1, 1.0).unwrap();
+        let first = ramped.ramp_to_speed(1, 1.0).unwrap();
+        let second = ramped.soft_stop(1).unwrap();
+        first.wait().unwrap();
+        second.wait().unwrap();
+        assert_eq!(0.0, ramped.board().lock().get_motor_speed(1).unwrap());
+
+        let handle = ramped.ramp_to_speed(1, 1.0).unwrap();
+        handle.cancel();
+        handle.wait().unwrap();
+        assert!(ramped.board().lock().get_motor_speed(1).unwrap() < 1.0);
+        assert!(ramped.ramp_to_speed(1, 2.0).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::backend::GpioBackend;
+use crate::board::{AMSBoard, BoardError};
+use crate::shared::SharedBoard;
+use core::time::Duration;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+impl<B: GpioBackend + 'static> SharedBoard<B> where AMSBoard<B>: Send {
+    /// Runs a motor at a signed speed for the specified duration without
+    /// blocking. See `AMSBoard::set_motor_speed` for the speed. The motor is
+    /// stopped by a background thread when the time elapses, even if the
+    /// handle is dropped. If other background work, like another timed run or
+    /// a ramp, is started on the same slot before the time elapses, it takes
+    /// over and this run does not stop the motor.
+    pub fn run_for(&self, n: usize, speed: f32, duration: Duration) -> Result<RunHandle, BoardError> {
+        println!("running motor {:?} at speed {:?} for {:?}", n, speed, duration);
+        let generation = self.claim(n)?;
+        self.lock().set_motor_speed(n, speed)?;
+
+        let timer = Arc::new((Mutex::new(RunTimer {
+            deadline: Instant::now() + duration,
+            cancelled: false,
+            finished: false,
+        }), Condvar::new()));
+        let shared = Arc::clone(&timer);
+        let board = self.clone();
+
+        let thread = thread::spawn(move || {
+            let (lock, condvar) = &*shared;
+            let mut t = lock.lock().unwrap();
+            while !t.cancelled {
+                let now = Instant::now();
+                if now >= t.deadline {
+                    break;
+                }
+                let timeout = t.deadline - now;
+                t = condvar.wait_timeout(t, timeout).unwrap().0;
+            }
+            t.finished = true;
+            drop(t);
+
+            let mut b = board.lock();
+            if !board.is_current(n, generation) {
+                return Ok(());
+            }
+
+            println!("timed run of motor {:?} has ended", n);
+            return b.stop_motor(n);
+        });
+
+        return Ok(RunHandle { timer, thread });
+    }
+}
+
+/// The deadline of a timed run.
+struct RunTimer {
+    /// the moment at which the motor is stopped
+    deadline: Instant,
+    /// whether the run was cancelled
+    cancelled: bool,
+    /// whether the time of the run has elapsed
+    finished: bool,
+}
+
+/// The handle of a timed run that is started with `SharedBoard::run_for`.
+/// Dropping the handle does not cancel the run, the motor is still stopped
+/// when the time elapses.
+pub struct RunHandle {
+    /// the deadline that is shared with the thread of the run
+    timer: Arc<(Mutex<RunTimer>, Condvar)>,
+    /// the thread that stops the motor
+    thread: JoinHandle<Result<(), BoardError>>,
+}
+
+impl RunHandle {
+    /// Stops the motor now instead of when the time elapses. Once newer
+    /// background work has taken over the slot, the run has no effect on the
+    /// motor anymore, so cancelling it does not stop the motor.
+    pub fn cancel(&self) {
+        let (lock, condvar) = &*self.timer;
+        lock.lock().unwrap().cancelled = true;
+        condvar.notify_all();
+    }
+
+    /// Extends the run by the specified duration. Returns false if the run
+    /// has already ended.
+    pub fn extend(&self, d: Duration) -> bool {
+        let (lock, condvar) = &*self.timer;
+        let mut t = lock.lock().unwrap();
+        if t.finished || t.cancelled {
+            return false;
+        }
+
+        t.deadline += d;
+        condvar.notify_all();
+        return true;
+    }
+
+    /// Returns the time that is left before the motor is stopped.
+    pub fn remaining(&self) -> Duration {
+        let (lock, _) = &*self.timer;
+        let t = lock.lock().unwrap();
+        if t.finished || t.cancelled {
+            return Duration::ZERO;
+        }
+
+        return t.deadline.saturating_duration_since(Instant::now());
+    }
+
+    /// Returns whether the motor has been stopped.
+    pub fn is_finished(&self) -> bool {
+        return self.thread.is_finished();
+    }
+
+    /// Waits until the motor has been stopped, and returns the error of the
+    /// board if it could not be stopped.
+    pub fn wait(self) -> Result<(), BoardError> {
+        return match self.thread.join() {
+            Ok(r) => r,
+            Err(e) => std::panic::resume_unwind(e),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::board::BoardType;
+    use crate::motor::Motor;
+
+    fn shared_board(backend: &MemoryBackend) -> SharedBoard<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 1).unwrap();
+        return SharedBoard::new(board);
+    }
+
+    /// Waits until the last action of the enable pin of M1 is the specified
+    /// action, for at most five seconds.
+    fn wait_for_action(backend: &MemoryBackend, action: PinAction) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && backend.actions(21).last() != Some(&action) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn run_for_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let start = Instant::now();
+        let handle = board.run_for(1, 1.0, Duration::from_millis(30)).unwrap();
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+        assert!(handle.extend(Duration::from_millis(20)));
+        handle.wait().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(21));
+    }
+
+    #[test]
+    fn cancel_and_drop_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let handle = board.run_for(1, 1.0, Duration::from_secs(60)).unwrap();
+        handle.cancel();
+        assert_eq!(Duration::ZERO, handle.remaining());
+        handle.wait().unwrap();
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(21));
+
+        // the motor is stopped when the handle is dropped
+        backend.clear();
+        drop(board.run_for(1, 1.0, Duration::from_millis(10)).unwrap());
+        wait_for_action(&backend, PinAction::Low);
+        assert_eq!(vec![PinAction::High, PinAction::Low], backend.actions(21));
+    }
+
+    #[test]
+    fn newer_run_takes_over_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let first = board.run_for(1, 1.0, Duration::from_millis(10)).unwrap();
+        let second = board.run_for(1, 1.0, Duration::from_secs(60)).unwrap();
+        first.wait().unwrap();
+        assert_eq!(vec![PinAction::High, PinAction::High], backend.actions(21));
+
+        // cancelling a run that was taken over does not stop the motor
+        let third = board.run_for(1, 1.0, Duration::from_secs(60)).unwrap();
+        second.cancel();
+        second.wait().unwrap();
+        assert_eq!(vec![PinAction::High; 3], backend.actions(21));
+
+        third.cancel();
+        third.wait().unwrap();
+        assert_eq!(PinAction::Low, *backend.actions(21).last().unwrap());
+
+        // a run that has ended can not be extended
+        backend.clear();
+        let fourth = board.run_for(1, 1.0, Duration::ZERO).unwrap();
+        wait_for_action(&backend, PinAction::Low);
+        assert!(!fourth.extend(Duration::from_secs(1)));
+        fourth.wait().unwrap();
+        assert!(matches!(board.run_for(5, 1.0, Duration::ZERO), Err(BoardError::MotorError(_))));
+    }
+}
@@ -1,11 +1,15 @@
-use rppal::gpio::{OutputPin, Gpio};
+use crate::backend::{self, GpioBackend, BackendError, PwmOutput, RppalBackend};
+use crate::board::BoardType;
+use crate::sysfs::PwmChip;
+use embedded_hal::pwm::SetDutyCycle;
 use std::time::Duration;
 use std::thread::sleep;
+use motor_shield_core::servo as servo_core;
 
 /// The servo struct. This is specifically written for the Tower Pro SG90.
-pub struct Servo {
-    /// The pin that is used to address the servo.
-    pin: Option<OutputPin>,
+pub struct Servo<B: GpioBackend = RppalBackend> {
+    /// The pwm output that is used to address the servo.
+    pin: Option<PwmOutput<B::Pwm>>,
     /// The configuration that the servo is currently operating in.
     config: ServoConfig,
 }
@@ -20,23 +24,81 @@ impl Servo {
 
     /// Returns a new servo from a configuration. A pin should also be provided.
     pub fn new_servo_from_config(cfg: ServoConfig, pin: u8) -> Servo {
-        let gpio = match Gpio::new() {
-            Ok(g) => g,
+        return Servo::new_servo_from_backend(&RppalBackend, cfg, pin);
+    }
+
+    /// Tests the servo range using a lower and upper bound on the pulse width.
+    /// A pin should also be specified.
+    pub fn test_range(pin: u8, low: Duration, up: Duration) {
+        let cycle = ServoConfig::PULSE_CYCLE;
+        let mut output_pin = match RppalBackend.pwm(pin, cycle) {
+            Ok(p) => p,
+            Err(e) => panic!("{:?}", e),
+        };
+
+        let mut cur = low;
+        while cur <= up {
+            println!("testing servo range width={:?} and cycle{:?}", cur, cycle);
+            sleep(Duration::from_millis(500));
+            if let Err(e) = backend::set_pulse_width(&mut output_pin, cycle, cur) {
+                panic!("{:?}", e);
+            }
+            cur += Duration::from_micros(100);
+        }
+
+        if let Err(e) = output_pin.set_duty_cycle_fully_off() {
+            panic!("{:?}", e);
+        }
+    }
+}
+
+impl<B: GpioBackend> Servo<B> {
+    /// Returns a new servo from a configuration, claiming the pin from the
+    /// specified backend. The pin is a BCM number.
+    pub fn new_servo_from_backend(backend: &B, cfg: ServoConfig, pin: u8) -> Servo<B> {
+        return match Servo::new_servo_with_type(backend, BoardType::BCM, cfg, pin) {
+            Ok(s) => s,
             Err(e) => panic!("{:?}", e),
         };
+    }
+
+    /// Returns a new servo from a configuration, claiming the pin from the
+    /// specified backend. The pin number is interpreted using the specified
+    /// board type, so power and ground pins of the header return an error.
+    pub fn new_servo_with_type(backend: &B, t: BoardType, cfg: ServoConfig, pin: u8) -> Result<Servo<B>, ServoError> {
+        let bcm = t.to_bcm(pin).map_err(|e| ServoError::Backend(e.into()))?;
+        let output_pin = backend.pwm(bcm, ServoConfig::PULSE_CYCLE).map_err(ServoError::Backend)?;
+        return Servo::new_servo_from_output(cfg, PwmOutput::Pin(output_pin));
+    }
 
-        let gpio_pin = match gpio.get(pin) {
-            Ok(gp) => gp,
+    /// Returns a new servo from a configuration and a pwm pin that was
+    /// created outside of the backend. The pin should run at the 50 hz
+    /// pulse cycle of the servo.
+    pub fn new_servo_from_pin(cfg: ServoConfig, pin: B::Pwm) -> Servo<B> {
+        return match Servo::new_servo_from_output(cfg, PwmOutput::Pin(pin)) {
+            Ok(s) => s,
             Err(e) => panic!("{:?}", e),
-        }; 
+        };
+    }
+
+    /// Returns a new servo from a configuration that is bound to a channel of
+    /// a hardware pwm chip instead of a pin of the backend. Hardware pwm does
+    /// not jitter like the software pwm of rppal.
+    pub fn new_servo_from_channel(cfg: ServoConfig, chip: &PwmChip, channel: u8) -> Result<Servo<B>, ServoError> {
+        let output = chip.channel(channel, ServoConfig::PULSE_CYCLE).map_err(ServoError::Backend)?;
+        return Servo::new_servo_from_output(cfg, PwmOutput::Channel(output));
+    }
 
-        let mut output_pin = gpio_pin.into_output_low();
-        output_pin.set_pwm(cfg.cycle, cfg.width);
+    /// Returns a new servo from a configuration and a pwm output, setting the
+    /// output to the pulse width of the configuration.
+    fn new_servo_from_output(cfg: ServoConfig, pin: PwmOutput<B::Pwm>) -> Result<Servo<B>, ServoError> {
+        let mut output_pin = pin;
+        backend::set_pulse_width(&mut output_pin, cfg.cycle, cfg.width).map_err(ServoError::Backend)?;
 
-        return Servo {
+        return Ok(Servo {
             pin: Some(output_pin),
             config: cfg
-        }
+        });
     }
 
     /// Rteurns a reference to the configuration that this servo is currently 
@@ -58,13 +120,14 @@ impl Servo {
         let pin = self.pin.as_mut().unwrap();
         let cfg = ServoConfig {
             cycle: Duration::from_millis(20),
-            width: width,
+            width,
             angle: a
         };
 
         self.config = cfg;
         println!("set servo to: cycle={:?} width={:?}", self.config.cycle, self.config.width);
-        pin.set_pwm(self.config.cycle, self.config.width);
+        backend::set_pulse_width(pin, self.config.cycle, self.config.width)
+            .map_err(ServoError::Backend)?;
 
         return Ok(());
     }
@@ -78,38 +141,18 @@ impl Servo {
     pub fn get_angle(self) -> u16 {
         return self.config.angle;
     }
-
-    /// Tests the servo range using a lower and upper bound on the pulse width.
-    /// A pin should also be specified.
-    pub fn test_range(pin: u8, low: Duration, up: Duration) {
-        let gpio = match Gpio::new() {
-            Ok(g) => g,
-            Err(e) => panic!("{:?}", e),
-        };
-
-        let gpio_pin = match gpio.get(pin) {
-            Ok(gp) => gp,
-            Err(e) => panic!("{:?}", e),
-        }; 
-
-        let mut output_pin = gpio_pin.into_output_low();
-        let mut cur = low;
-        let cycle = ServoConfig::PULSE_CYCLE;
-        while cur <= up {
-            println!("testing servo range width={:?} and cycle{:?}", cur, cycle);
-            sleep(Duration::from_millis(500));
-            output_pin.set_pwm(cycle, cur);
-            cur = cur + Duration::from_micros(100);
-        }
-
-        output_pin.set_low();
-    }
 }
 
+#[derive(Debug)]
 pub enum ServoError {
     PinNotSet,
+    ServoNotFound,
+    ServoIndexOutOfBounds,
+    InvalidCalibration,
+    Backend(BackendError),
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct ServoConfig {
     cycle: Duration,
     width: Duration,
@@ -118,14 +161,12 @@ pub struct ServoConfig {
 
 impl ServoConfig {
     /// The minimum pulse width of the servo, used to calculate angles.
-    const MIN: f32 = 600.0; 
+    const MIN: f32 = servo_core::MIN;
     /// The maximum pulse width of the servo, used to calculate angles.
-    const MAX: f32 = 2550.0;
-    /// The maximum angle that the servo supports.
-    const MAX_ANGLE: u16 = 180; 
-    /// The pulse cycle of the servo. This is made for servos that use a 
+    const MAX: f32 = servo_core::MAX;
+    /// The pulse cycle of the servo. This is made for servos that use a
     /// frequency of 50 Herz or 20 milliseconds.
-    const PULSE_CYCLE: Duration = Duration::from_millis(20); 
+    const PULSE_CYCLE: Duration = servo_core::PULSE_CYCLE;
 
     /// Returns a new configuration with a specified pulse width. The cycle
     /// is set to the 50 hz default cycle length.
@@ -140,7 +181,7 @@ impl ServoConfig {
 
         return ServoConfig {
             cycle: ServoConfig::PULSE_CYCLE,
-            width: width,
+            width,
             angle: 0
         }
     }
@@ -148,14 +189,40 @@ impl ServoConfig {
     /// Returns a new default configuration with the default angle. This is 
     /// currently set locally to 90 degrees.
     pub fn new_default() -> ServoConfig {
-        const default_angle: u16 = 90;
+        const DEFAULT_ANGLE: u16 = 90;
         return ServoConfig {
             cycle: ServoConfig::PULSE_CYCLE,
-            width: ServoConfig::calc_width_from_angle(default_angle),
-            angle: default_angle,
+            width: ServoConfig::calc_width_from_angle(DEFAULT_ANGLE),
+            angle: DEFAULT_ANGLE,
         }
     }
 
+    /// Returns a new configuration with a pulse width that was calculated
+    /// for the angle outside of this configuration, like for a calibrated
+    /// servo.
+    pub(crate) fn new_calibrated(width: Duration, angle: u16) -> ServoConfig {
+        return ServoConfig {
+            cycle: ServoConfig::PULSE_CYCLE,
+            width,
+            angle,
+        }
+    }
+
+    /// Returns the pulse cycle of this configuration.
+    pub fn cycle(&self) -> Duration {
+        return self.cycle;
+    }
+
+    /// Returns the pulse width of this configuration.
+    pub fn width(&self) -> Duration {
+        return self.width;
+    }
+
+    /// Returns the angle of this configuration.
+    pub fn angle(&self) -> u16 {
+        return self.angle;
+    }
+
     /// Returns a new configuration based on the specified pulse width.
     pub fn new_config_from_width(w: Duration) -> ServoConfig {
         return ServoConfig::new(w);
@@ -170,20 +237,6 @@ impl ServoConfig {
     /// Returns the duration of a pulse width calculated from the specified
     /// angle.
     fn calc_width_from_angle(a: u16) -> Duration {
-        if a > ServoConfig::MAX_ANGLE {
-            panic!("angle can be at most 180");
-        }
-
-        // the range over which the servo can operate
-        let range: f32 = ServoConfig::MAX - ServoConfig::MIN;
-        // the percentage of width over the total range
-        let prcnt: f32 = (a as f32) / (ServoConfig::MAX_ANGLE as f32); 
-        // the scalar that should go on top of the minimum value
-        let scalar: f32 = prcnt * (range as f32);
-        // the final width of the pulse
-        let width: u64 = (ServoConfig::MIN as u64) + (scalar as u64);
-
-        return Duration::from_micros(width);
+        return servo_core::width_from_angle(a);
     }
 }
-
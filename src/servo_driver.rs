@@ -0,0 +1,230 @@
+use crate::backend::BackendError;
+use crate::i2c::StdDelay;
+use crate::servo::{ServoConfig, ServoError};
+use embedded_hal::i2c::I2c;
+use motor_shield_core::pca9685::{self, Pca9685, Pca9685Error, CHANNELS};
+use motor_shield_core::servo as servo_core;
+use core::time::Duration;
+
+/// The pulse widths at which a servo reaches 0 and 180 degrees. Servos of
+/// the same type differ a bit, so every channel can be calibrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    /// The pulse width at 0 degrees.
+    pub min: Duration,
+    /// The pulse width at 180 degrees.
+    pub max: Duration,
+}
+
+impl Default for Calibration {
+    /// Returns the calibration of the Tower Pro SG90, which is also used by
+    /// `Servo`.
+    fn default() -> Calibration {
+        return Calibration {
+            min: Duration::from_micros(servo_core::MIN as u64),
+            max: Duration::from_micros(servo_core::MAX as u64),
+        };
+    }
+}
+
+/// A PCA9685 that drives up to 16 servos, like the Adafruit 16-channel servo
+/// driver. All channels share the 50 Hz pulse cycle of the chip and every
+/// channel has its own calibration.
+pub struct ServoDriver<I: I2c> {
+    /// the pwm driver of the servos
+    driver: Pca9685<I>,
+    /// the prescaler that is set on the chip
+    prescale: u8,
+    /// the calibration of every channel
+    calibrations: [Calibration; 16],
+    /// the configuration of every channel, if it was set
+    configs: [Option<ServoConfig>; 16],
+}
+
+impl<I: I2c> ServoDriver<I> {
+    /// The default address of the driver, without any address jumpers.
+    pub const DEFAULT_ADDRESS: u8 = 0x40;
+    /// The pwm frequency of the servos, which is the 20 ms pulse cycle.
+    pub const FREQUENCY: u32 = 50;
+
+    /// Creates a new servo driver on the default address.
+    pub fn new(i2c: I) -> ServoDriver<I> {
+        return ServoDriver::with_address(i2c, Self::DEFAULT_ADDRESS);
+    }
+
+    /// Creates a new servo driver on the specified address. The driver has
+    /// to be initialized with `init` before servos can be moved.
+    pub fn with_address(i2c: I, address: u8) -> ServoDriver<I> {
+        println!("creating new servo driver on address {:#04x}", address);
+        let prescale = match pca9685::prescale_for(Self::FREQUENCY) {
+            Some(p) => p,
+            None => panic!("servo frequency is out of range"),
+        };
+
+        return ServoDriver {
+            driver: Pca9685::new(i2c, address),
+            prescale,
+            calibrations: [Calibration::default(); 16],
+            configs: [None; 16],
+        };
+    }
+
+    /// Initializes the PCA9685 and sets the prescaler for the 50 Hz pulse
+    /// cycle of the servos.
+    pub fn init(&mut self) -> Result<(), ServoError> {
+        println!("initializing servo driver with prescale {:?}", self.prescale);
+        self.driver.init().map_err(ServoError::pca9685)?;
+        self.driver.set_frequency(Self::FREQUENCY, &mut StdDelay).map_err(ServoError::pca9685)?;
+        return Ok(());
+    }
+
+    /// Returns the bus of the driver, consuming the driver.
+    pub fn release(self) -> I {
+        return self.driver.release();
+    }
+
+    /// Returns the prescaler that the driver sets for the pulse cycle.
+    pub fn get_prescale(&self) -> u8 {
+        return self.prescale;
+    }
+
+    /// Sets the pulse widths at which the servo on the specified channel
+    /// reaches 0 and 180 degrees. The maximum should be longer than the
+    /// minimum and fit in the pulse cycle.
+    pub fn set_calibration(&mut self, channel: u8, c: Calibration) -> Result<(), ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        if c.min >= c.max || c.max > ServoConfig::new_default().cycle() {
+            return Err(ServoError::InvalidCalibration);
+        }
+
+        println!("calibrating servo {:?} to min={:?} max={:?}", channel, c.min, c.max);
+        self.calibrations[channel as usize] = c;
+        return Ok(());
+    }
+
+    /// Returns the calibration of the specified channel.
+    pub fn get_calibration(&self, channel: u8) -> Result<Calibration, ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        return Ok(self.calibrations[channel as usize]);
+    }
+
+    /// Returns the configuration that the servo on the specified channel is
+    /// currently operating in, or `None` if it was not moved yet.
+    pub fn get_config(&self, channel: u8) -> Result<Option<&ServoConfig>, ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        return Ok(self.configs[channel as usize].as_ref());
+    }
+
+    /// Sets the angle of the servo on the specified channel to the specified
+    /// degrees, using the calibration of the channel to calculate the pulse
+    /// width.
+    pub fn set_angle(&mut self, channel: u8, a: u16) -> Result<(), ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        let c = self.calibrations[channel as usize];
+        let width = servo_core::width_from_angle_between(c.min, c.max, a);
+        return self.set_config(channel, ServoConfig::new_calibrated(width, a));
+    }
+
+    /// Sets the servo on the specified channel to the pulse width of the
+    /// configuration. The calibration is not used for this.
+    pub fn set_config(&mut self, channel: u8, cfg: ServoConfig) -> Result<(), ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        let counts = pca9685::counts_for_width(self.prescale, cfg.width());
+        println!("set servo {:?} to: width={:?} counts={:?}", channel, cfg.width(), counts);
+        self.driver.set_pwm(channel, 0, counts).map_err(ServoError::pca9685)?;
+        self.configs[channel as usize] = Some(cfg);
+
+        return Ok(());
+    }
+
+    /// Turns off the pulses of the specified channel, so the servo no longer
+    /// holds its position.
+    pub fn disable(&mut self, channel: u8) -> Result<(), ServoError> {
+        ServoDriver::<I>::check_channel(channel)?;
+        println!("disabling servo {:?}", channel);
+        self.driver.set_pin(channel, false).map_err(ServoError::pca9685)?;
+        self.configs[channel as usize] = None;
+
+        return Ok(());
+    }
+
+    /// Checks that the specified channel exists on the chip.
+    fn check_channel(channel: u8) -> Result<(), ServoError> {
+        if channel >= CHANNELS {
+            return Err(ServoError::ServoIndexOutOfBounds);
+        }
+
+        return Ok(());
+    }
+}
+
+impl ServoError {
+    /// Converts an error of the PCA9685 into a servo error.
+    fn pca9685<E: embedded_hal::i2c::Error>(e: Pca9685Error<E>) -> ServoError {
+        return match e {
+            Pca9685Error::Bus(e) => ServoError::Backend(BackendError::i2c(e)),
+            Pca9685Error::InvalidChannel => ServoError::ServoIndexOutOfBounds,
+            Pca9685Error::InvalidFrequency => ServoError::Backend(BackendError::Unsupported),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::MemoryPca9685;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+    use motor_shield_core::pca9685::{LED0_ON_L, MODE1, MODE1_AI, MODE1_RESTART, MODE1_SLEEP, MODE2, MODE2_OUTDRV, PRESCALE};
+
+    #[test]
+    fn init_and_angle_writes_test() {
+        let expectations = [
+            Transaction::write(0x40, vec![MODE2, MODE2_OUTDRV]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI]),
+            Transaction::write_read(0x40, vec![MODE1], vec![MODE1_AI]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI | MODE1_SLEEP]),
+            Transaction::write(0x40, vec![PRESCALE, 121]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI]),
+            Transaction::write(0x40, vec![MODE1, MODE1_AI | MODE1_RESTART]),
+            // 1575 us at 90 degrees is 322 steps, 0x0142
+            Transaction::write(0x40, vec![LED0_ON_L + 4 * 5, 0, 0, 0x42, 0x01]),
+            Transaction::write(0x40, vec![LED0_ON_L + 4 * 5, 0, 0, 0x00, 0x10]),
+        ];
+        let mut i2c = Mock::new(&expectations);
+        let mut servos = ServoDriver::new(i2c.clone());
+        servos.init().unwrap();
+        servos.set_angle(5, 90).unwrap();
+        assert_eq!(90, servos.get_config(5).unwrap().unwrap().angle());
+        servos.disable(5).unwrap();
+        assert!(servos.get_config(5).unwrap().is_none());
+        i2c.done();
+    }
+
+    #[test]
+    fn calibration_test() {
+        let chip = MemoryPca9685::new(0x40);
+        let mut servos = ServoDriver::new(chip.clone());
+        servos.init().unwrap();
+        assert_eq!(121, chip.register(PRESCALE));
+
+        let c = Calibration { min: Duration::from_micros(500), max: Duration::from_micros(2500) };
+        servos.set_calibration(15, c).unwrap();
+        servos.set_angle(15, 180).unwrap();
+        servos.set_angle(0, 180).unwrap();
+        assert_eq!(512, chip.duty(15));
+        assert_eq!(522, chip.duty(0));
+        assert_eq!(Duration::from_micros(2500), servos.get_config(15).unwrap().unwrap().width());
+
+        let cfg = ServoConfig::new_config_from_width(Duration::from_micros(1000));
+        servos.set_config(1, cfg).unwrap();
+        assert_eq!(204, chip.duty(1));
+    }
+
+    #[test]
+    fn invalid_channel_test() {
+        let mut servos = ServoDriver::new(MemoryPca9685::new(0x40));
+        assert!(matches!(servos.set_angle(16, 90), Err(ServoError::ServoIndexOutOfBounds)));
+        let c = Calibration { min: Duration::from_micros(2500), max: Duration::from_micros(500) };
+        assert!(matches!(servos.set_calibration(0, c), Err(ServoError::InvalidCalibration)));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::{AMSBoard, BoardError};
+use crate::motor::MotorError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// # A board that is shared between threads
+///
+/// Ramps, speed control loops, timed runs, drives and steppers all take a
+/// shared board, so they can be combined on the same shield. Cloning the
+/// handle shares the same board, which sits behind a mutex that is only
+/// locked while the board is used. Background work on a motor slot claims
+/// the slot, and stops once newer background work claims the same slot, so
+/// a ramp can take over from a timed run or a speed control loop.
+pub struct SharedBoard<B: GpioBackend = RppalBackend> {
+    /// the board that is shared
+    board: Arc<Mutex<AMSBoard<B>>>,
+    /// the generation of the background work of every slot
+    generations: Arc<[AtomicU64; 4]>,
+}
+
+impl<B: GpioBackend> Clone for SharedBoard<B> {
+    fn clone(&self) -> SharedBoard<B> {
+        return SharedBoard {
+            board: Arc::clone(&self.board),
+            generations: Arc::clone(&self.generations),
+        };
+    }
+}
+
+impl<B: GpioBackend> SharedBoard<B> {
+    /// Shares a board.
+    pub fn new(board: AMSBoard<B>) -> SharedBoard<B> {
+        return SharedBoard {
+            board: Arc::new(Mutex::new(board)),
+            generations: Arc::new([AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)]),
+        };
+    }
+
+    /// Locks the board until the guard is dropped. Background work waits
+    /// for the guard, so it should not be held longer than needed.
+    pub fn lock(&self) -> MutexGuard<'_, AMSBoard<B>> {
+        return self.board.lock().unwrap();
+    }
+
+    /// Claims a motor slot for background work, so the work that was started
+    /// on the slot before stops. Returns the generation of the new work.
+    pub(crate) fn claim(&self, n: usize) -> Result<u64, BoardError> {
+        check_slot(n)?;
+        return Ok(self.generations[n - 1].fetch_add(1, Ordering::SeqCst) + 1);
+    }
+
+    /// Returns whether the background work of the specified generation still
+    /// owns the motor slot.
+    pub(crate) fn is_current(&self, n: usize, generation: u64) -> bool {
+        return self.generations[n - 1].load(Ordering::SeqCst) == generation;
+    }
+}
+
+/// Checks that the specified motor slot exists.
+pub(crate) fn check_slot(n: usize) -> Result<(), BoardError> {
+    if !(1..=4).contains(&n) {
+        return Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds));
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,267 @@
+use crate::backend::BackendError;
+use crate::board::BoardError;
+use crate::i2c::StdDelay;
+use crate::motor::{Direction, MotorConfig, MotorError, Status};
+use embedded_hal::i2c::I2c;
+use motor_shield_core::pca9685::{Pca9685, Pca9685Error, RESOLUTION};
+use motor_shield_core::pwm;
+use core::time::Duration;
+
+/// The channels of the PCA9685 that drive a motor slot of the shield. The
+/// pwm channel drives the enable of the TB6612 and the two input channels
+/// set the direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotorChannels {
+    pub pwm: u8,
+    pub in1: u8,
+    pub in2: u8,
+}
+
+/// The channels of the M1 to M4 slots on the shield.
+pub const MOTOR_CHANNELS: [MotorChannels; 4] = [
+    MotorChannels { pwm: 8, in1: 10, in2: 9 },
+    MotorChannels { pwm: 13, in1: 11, in2: 12 },
+    MotorChannels { pwm: 2, in1: 4, in2: 3 },
+    MotorChannels { pwm: 7, in1: 5, in2: 6 },
+];
+
+/// # The Adafruit Motor Shield V2
+///
+/// The V2 shield drives the directions and the pwm of all motors through a
+/// PCA9685 on the I2C bus, instead of a 74HC595 and pwm pins. The motor
+/// slots are fixed on the shield, so there is no need to set motors.
+pub struct MotorShieldV2<I: I2c> {
+    /// the pwm driver of the shield
+    driver: Pca9685<I>,
+    /// the directions of the motor slots
+    directions: [Direction; 4],
+    /// the status of the motor slots
+    status: [Status; 4],
+}
+
+impl<I: I2c> MotorShieldV2<I> {
+    /// The default address of the shield, without any address jumpers.
+    pub const DEFAULT_ADDRESS: u8 = 0x60;
+    /// The pwm frequency of the motors.
+    pub const FREQUENCY: u32 = 1600;
+
+    /// Creates a new shield on the default address.
+    pub fn new(i2c: I) -> MotorShieldV2<I> {
+        return MotorShieldV2::with_address(i2c, Self::DEFAULT_ADDRESS);
+    }
+
+    /// Creates a new shield on the specified address. The shield has to be
+    /// initialized with `init` before motors can be started.
+    pub fn with_address(i2c: I, address: u8) -> MotorShieldV2<I> {
+        println!("creating new V2 shield on address {:#04x}", address);
+        return MotorShieldV2 {
+            driver: Pca9685::new(i2c, address),
+            directions: [Direction::Clockwise; 4],
+            status: [Status::Idle; 4],
+        };
+    }
+
+    /// Initializes the PCA9685 of the shield, sets the pwm frequency and
+    /// turns all channels off.
+    pub fn init(&mut self) -> Result<(), BoardError> {
+        println!("initializing V2 shield");
+        self.driver.init().map_err(BoardError::pca9685)?;
+        self.driver.set_frequency(Self::FREQUENCY, &mut StdDelay).map_err(BoardError::pca9685)?;
+        for n in 1..=4 {
+            let channels = MOTOR_CHANNELS[n - 1];
+            self.driver.set_pin(channels.pwm, false).map_err(BoardError::pca9685)?;
+            self.driver.set_pin(channels.in1, false).map_err(BoardError::pca9685)?;
+            self.driver.set_pin(channels.in2, false).map_err(BoardError::pca9685)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the bus of the shield, consuming the shield.
+    pub fn release(self) -> I {
+        return self.driver.release();
+    }
+
+    /// Returns the direction of the specified motor slot.
+    pub fn get_direction(&self, n: usize) -> Result<Direction, MotorError> {
+        MotorShieldV2::<I>::check_slot(n)?;
+        return Ok(self.directions[n - 1]);
+    }
+
+    /// Returns the status of the specified motor slot.
+    pub fn get_status(&self, n: usize) -> Result<Status, MotorError> {
+        MotorShieldV2::<I>::check_slot(n)?;
+        return Ok(self.status[n - 1]);
+    }
+
+    /// Inverts the direction of a motor. If the motor is running, it will
+    /// run in the inverted direction immediately.
+    pub fn invert_motor_direction(&mut self, n: usize) -> Result<(), BoardError> {
+        println!("inverting motor directions");
+        let d = self.get_direction(n).map_err(BoardError::MotorError)?;
+        return self.change_motor_direction(n, d.inverted());
+    }
+
+    /// Changes the direction of a motor. If the motor is running, it will
+    /// run in the new direction immediately.
+    pub fn change_motor_direction(&mut self, n: usize, d: Direction) -> Result<(), BoardError> {
+        MotorShieldV2::<I>::check_slot(n).map_err(BoardError::MotorError)?;
+        println!("changing direction of motor {:?} to {:?}", n, d);
+        self.directions[n - 1] = d;
+        if let Status::Idle = self.status[n - 1] {
+            return Ok(());
+        }
+
+        return self.write_direction(n);
+    }
+
+    /// Starts a specified motor using a motor configuration.
+    pub fn start_motor_config(&mut self, n: usize, mc: MotorConfig) -> Result<(), BoardError> {
+        println!("start motor {:?} with config {:?}", n, mc);
+        MotorShieldV2::<I>::check_slot(n).map_err(BoardError::MotorError)?;
+        if !mc.is_full() && mc.width() > mc.cycle() {
+            return Err(BoardError::MotorError(MotorError::PWMDurationTooHigh));
+        }
+
+        self.write_direction(n)?;
+        let duty = match mc.is_full() {
+            true => RESOLUTION,
+            false => pwm::duty_from_width(RESOLUTION, mc.cycle(), mc.width()),
+        };
+
+        let channels = MOTOR_CHANNELS[n - 1];
+        self.driver.set_duty(channels.pwm, duty).map_err(BoardError::pca9685)?;
+        self.status[n - 1] = match mc.is_full() {
+            true => Status::Running,
+            false => Status::PWM,
+        };
+
+        return Ok(());
+    }
+
+    /// Starts a specified motor using a pulse cycle and pulse width. Only the
+    /// ratio between the two is used, the pwm runs at `FREQUENCY`.
+    pub fn start_motor_pwm(&mut self, n: usize, cycle: Duration, width: Duration) -> Result<(), BoardError> {
+        println!("start motor {:?} with pwm cycle {:?} and width {:?}", n, cycle, width);
+        return self.start_motor_config(n, MotorConfig::new_pwm(cycle, width));
+    }
+
+    /// Starts a specified motor on full speed.
+    pub fn start_motor_full(&mut self, n: usize) -> Result<(), BoardError> {
+        println!("start motor {:?} at full speed", n);
+        return self.start_motor_config(n, MotorConfig::new_full());
+    }
+
+    /// Stops a specified motor by turning its pwm channel off. The direction
+    /// inputs are released as well, so the motor coasts to a stop.
+    pub fn stop_motor(&mut self, n: usize) -> Result<(), BoardError> {
+        MotorShieldV2::<I>::check_slot(n).map_err(BoardError::MotorError)?;
+        println!("stopping motor {:?}", n);
+        let channels = MOTOR_CHANNELS[n - 1];
+        self.driver.set_pin(channels.pwm, false).map_err(BoardError::pca9685)?;
+        self.driver.set_pin(channels.in1, false).map_err(BoardError::pca9685)?;
+        self.driver.set_pin(channels.in2, false).map_err(BoardError::pca9685)?;
+        self.status[n - 1] = Status::Idle;
+
+        return Ok(());
+    }
+
+    /// Writes the direction of a motor slot to its input channels.
+    fn write_direction(&mut self, n: usize) -> Result<(), BoardError> {
+        let channels = MOTOR_CHANNELS[n - 1];
+        let forward = self.directions[n - 1] == Direction::Clockwise;
+        // one input is always set low first, so both inputs are never high
+        // at the same time while switching
+        let (low, high) = match forward {
+            true => (channels.in2, channels.in1),
+            false => (channels.in1, channels.in2),
+        };
+        self.driver.set_pin(low, false).map_err(BoardError::pca9685)?;
+        self.driver.set_pin(high, true).map_err(BoardError::pca9685)?;
+
+        return Ok(());
+    }
+
+    /// Checks that the specified motor slot exists on the shield.
+    fn check_slot(n: usize) -> Result<(), MotorError> {
+        if !(1..=4).contains(&n) {
+            return Err(MotorError::MotorIndexOutOfBounds);
+        }
+
+        return Ok(());
+    }
+}
+
+impl BoardError {
+    /// Converts an error of the PCA9685 into a board error.
+    pub(crate) fn pca9685<E: embedded_hal::i2c::Error>(e: Pca9685Error<E>) -> BoardError {
+        return match e {
+            Pca9685Error::Bus(e) => BoardError::Backend(BackendError::i2c(e)),
+            _ => BoardError::Backend(BackendError::Unsupported),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::MemoryPca9685;
+    use motor_shield_core::pca9685::PRESCALE;
+
+    #[test]
+    fn init_test() {
+        let chip = MemoryPca9685::new(0x60);
+        let mut shield = MotorShieldV2::new(chip.clone());
+        shield.init().unwrap();
+        assert_eq!(3, chip.register(PRESCALE));
+        for channel in 0..16 {
+            assert_eq!(0, chip.duty(channel));
+        }
+    }
+
+    #[test]
+    fn motor_slots_test() {
+        let chip = MemoryPca9685::new(0x60);
+        let mut shield = MotorShieldV2::new(chip.clone());
+        shield.init().unwrap();
+
+        shield.start_motor_full(1).unwrap();
+        assert!(chip.is_high(8));
+        assert!(chip.is_high(10));
+        assert!(!chip.is_high(9));
+
+        shield.start_motor_pwm(3, Duration::from_millis(20), Duration::from_millis(5)).unwrap();
+        assert_eq!(1024, chip.duty(2));
+        assert!(matches!(shield.get_status(3), Ok(Status::PWM)));
+
+        // changing the direction of a running motor is applied immediately
+        shield.change_motor_direction(1, Direction::Counterclockwise).unwrap();
+        assert!(!chip.is_high(10));
+        assert!(chip.is_high(9));
+        assert!(chip.is_high(8));
+
+        shield.stop_motor(1).unwrap();
+        assert_eq!(0, chip.duty(8));
+        assert_eq!(0, chip.duty(9));
+        assert!(matches!(shield.get_status(1), Ok(Status::Idle)));
+
+        // an idle motor only stores its direction
+        chip.clear();
+        shield.invert_motor_direction(4).unwrap();
+        assert!(chip.writes().is_empty());
+        assert!(matches!(shield.get_direction(4), Ok(Direction::Counterclockwise)));
+    }
+
+    #[test]
+    fn invalid_motor_test() {
+        let mut shield = MotorShieldV2::new(MemoryPca9685::new(0x60));
+        assert!(matches!(shield.start_motor_full(5),
+            Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds))));
+        assert!(matches!(shield.start_motor_pwm(1, Duration::from_millis(20), Duration::from_millis(30)),
+            Err(BoardError::MotorError(MotorError::PWMDurationTooHigh))));
+
+        // a shield on another address does not acknowledge
+        let mut shield = MotorShieldV2::with_address(MemoryPca9685::new(0x61), 0x60);
+        assert!(matches!(shield.init(), Err(BoardError::Backend(BackendError::Bus(_)))));
+    }
+}
@@ -0,0 +1,435 @@
+use crate::backend::{BackendError, GpioBackend, RppalBackend};
+use crate::board::{AMSBoard, BoardError};
+use crate::encoder::Encoder;
+use crate::motor::MotorError;
+use crate::shared::{self, SharedBoard};
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+pub use motor_shield_core::pid::Pid;
+
+/// A source of the measured speed of a motor.
+pub trait SpeedFeedback: Send {
+    /// Returns the speed in counts per second.
+    fn counts_per_second(&mut self) -> Result<f32, BackendError>;
+
+    /// Returns the number of counts in one revolution, or zero if it is not
+    /// known. This is needed for a target in revolutions per minute.
+    fn counts_per_revolution(&self) -> u32 {
+        return 0;
+    }
+}
+
+impl SpeedFeedback for Encoder {
+    fn counts_per_second(&mut self) -> Result<f32, BackendError> {
+        if !self.is_interrupt_driven() {
+            self.poll()?;
+        }
+
+        return Ok(self.velocity());
+    }
+
+    fn counts_per_revolution(&self) -> u32 {
+        return self.get_counts_per_revolution();
+    }
+}
+
+/// The source of the speed that a controlled motor is measured with.
+pub enum Feedback {
+    /// The encoder that is attached to the motor slot of the board.
+    Encoder,
+    /// Any other source of the speed.
+    Custom(Box<dyn SpeedFeedback>),
+}
+
+/// The target speed of a controlled motor. A negative speed runs the motor
+/// counterclockwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetSpeed {
+    CountsPerSecond(f32),
+    Rpm(f32),
+}
+
+impl TargetSpeed {
+    /// Returns the target in counts per second, or `None` if the target is
+    /// not a number or is in revolutions per minute while the counts per
+    /// revolution are not known.
+    pub fn counts_per_second(&self, counts_per_revolution: u32) -> Option<f32> {
+        let cps = match *self {
+            TargetSpeed::CountsPerSecond(cps) => cps,
+            TargetSpeed::Rpm(_) if counts_per_revolution == 0 => return None,
+            TargetSpeed::Rpm(rpm) => rpm * counts_per_revolution as f32 / 60.0,
+        };
+
+        if !cps.is_finite() {
+            return None;
+        }
+
+        return Some(cps);
+    }
+}
+
+/// # A board with closed-loop speed control
+///
+/// Two motors of the same type do not run at the same speed with the same
+/// duty cycle. The speed controller measures the speed of a motor and
+/// corrects its duty cycle with a PID controller on a fixed-rate loop in a
+/// background thread. Every motor slot has its own controller. The output of
+/// a controller is a signed speed like that of `set_motor_speed`, so the
+/// gains convert counts per second into duty cycle. While the output keeps
+/// its sign, an update only changes the duty cycle of the motor and does not
+/// latch the directions again. Like the ramped board, the shared board is
+/// only locked during an update.
+pub struct SpeedController<B: GpioBackend = RppalBackend> {
+    /// the board that the motors are on
+    board: SharedBoard<B>,
+    /// the controller of every slot
+    pids: [Pid; 4],
+    /// the time between two updates of a loop
+    interval: Duration,
+}
+
+impl<B: GpioBackend + 'static> SpeedController<B> where AMSBoard<B>: Send {
+    /// The time between two updates of a loop that is used until it is set.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Creates a speed controller on a shared board.
+    pub fn new(board: &SharedBoard<B>) -> SpeedController<B> {
+        return SpeedController {
+            board: board.clone(),
+            pids: [Pid::new(0.0, 0.0, 0.0); 4],
+            interval: Self::DEFAULT_INTERVAL,
+        };
+    }
+
+    /// Returns the shared board.
+    pub fn board(&self) -> SharedBoard<B> {
+        return self.board.clone();
+    }
+
+    /// Sets the controller of a motor slot. It is used by the loops that are
+    /// started afterwards.
+    pub fn set_pid(&mut self, n: usize, pid: Pid) -> Result<(), BoardError> {
+        shared::check_slot(n)?;
+        self.pids[n - 1] = pid;
+        return Ok(());
+    }
+
+    /// Sets the lowest and the highest speed that the controller of a motor
+    /// slot passes to the board, within -1.0 and 1.0. A lowest speed of 0.0
+    /// keeps the motor from reversing.
+    pub fn set_output_limits(&mut self, n: usize, min: f32, max: f32) -> Result<(), BoardError> {
+        shared::check_slot(n)?;
+        if !(-1.0..=1.0).contains(&min) || !(-1.0..=1.0).contains(&max) || !self.pids[n - 1].set_limits(min, max) {
+            return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+        }
+
+        return Ok(());
+    }
+
+    /// Sets the time between two updates of a loop.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Controls the speed of a motor towards the target in the background,
+    /// measuring it with the feedback. Other background work that is still
+    /// running on the same slot, like a loop, is stopped.
+    pub fn control(&self, n: usize, target: TargetSpeed, feedback: Feedback) -> Result<SpeedHandle, BoardError> {
+        shared::check_slot(n)?;
+        let counts_per_revolution = {
+            let mut board = self.board.lock();
+            board.get_motor(n).map_err(BoardError::MotorError)?;
+            match &feedback {
+                Feedback::Encoder => board.get_encoder(n).map_err(BoardError::MotorError)?.get_counts_per_revolution(),
+                Feedback::Custom(f) => f.counts_per_revolution(),
+            }
+        };
+
+        let cps = match target.counts_per_second(counts_per_revolution) {
+            Some(cps) => cps,
+            None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+        };
+
+        println!("controlling speed of motor {:?} to {:?} counts per second", n, cps);
+        let generation = self.board.claim(n)?;
+        let board = self.board.clone();
+        let state = Arc::new(Mutex::new(LoopState {
+            target: cps,
+            counts_per_revolution,
+            stopped: false,
+            measured: 0.0,
+            output: 0.0,
+        }));
+        let shared = Arc::clone(&state);
+        let mut pid = self.pids[n - 1];
+        let interval = self.interval;
+
+        let thread = thread::spawn(move || {
+            let mut feedback = feedback;
+            let dt = interval.as_secs_f32();
+            let mut next = Instant::now();
+            pid.reset();
+            loop {
+                {
+                    let mut board_lock = board.lock();
+                    // newer work on the same slot takes over
+                    if !board.is_current(n, generation) {
+                        return Ok(());
+                    }
+
+                    let (target, stopped) = {
+                        let s = shared.lock().unwrap();
+                        (s.target, s.stopped)
+                    };
+                    if stopped {
+                        println!("speed control of motor {:?} has stopped", n);
+                        return board_lock.stop_motor(n);
+                    }
+
+                    let measured = match &mut feedback {
+                        Feedback::Encoder => board_lock.get_encoder(n).map_err(BoardError::MotorError)?.counts_per_second(),
+                        Feedback::Custom(f) => f.counts_per_second(),
+                    }.map_err(BoardError::Backend)?;
+                    let output = pid.update(target, measured, dt);
+                    board_lock.update_motor_speed(n, output)?;
+
+                    let mut s = shared.lock().unwrap();
+                    s.measured = measured;
+                    s.output = output;
+                }
+
+                next += interval;
+                let now = Instant::now();
+                match next > now {
+                    true => thread::sleep(next - now),
+                    false => next = now,
+                };
+            }
+        });
+
+        return Ok(SpeedHandle { state, thread });
+    }
+}
+
+/// The state of a loop that is shared with its handle.
+struct LoopState {
+    /// the target in counts per second
+    target: f32,
+    /// the counts per revolution of the feedback
+    counts_per_revolution: u32,
+    /// whether the loop should stop the motor at its next update
+    stopped: bool,
+    /// the speed that was measured at the last update
+    measured: f32,
+    /// the speed that was passed to the board at the last update
+    output: f32,
+}
+
+/// The handle of a speed control loop that runs in the background. Dropping
+/// the handle does not stop the loop.
+pub struct SpeedHandle {
+    /// the state that is shared with the loop
+    state: Arc<Mutex<LoopState>>,
+    /// the thread that runs the loop
+    thread: JoinHandle<Result<(), BoardError>>,
+}
+
+impl SpeedHandle {
+    /// Changes the target of the loop.
+    pub fn set_target(&self, target: TargetSpeed) -> Result<(), BoardError> {
+        let mut s = self.state.lock().unwrap();
+        s.target = match target.counts_per_second(s.counts_per_revolution) {
+            Some(cps) => cps,
+            None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+        };
+
+        return Ok(());
+    }
+
+    /// Returns the target of the loop in counts per second.
+    pub fn target(&self) -> f32 {
+        return self.state.lock().unwrap().target;
+    }
+
+    /// Returns the speed in counts per second that was measured at the last
+    /// update.
+    pub fn measured(&self) -> f32 {
+        return self.state.lock().unwrap().measured;
+    }
+
+    /// Returns the speed that was passed to the board at the last update.
+    pub fn output(&self) -> f32 {
+        return self.state.lock().unwrap().output;
+    }
+
+    /// Stops the loop and the motor at the next update.
+    pub fn stop(&self) {
+        self.state.lock().unwrap().stopped = true;
+    }
+
+    /// Returns whether the loop has ended.
+    pub fn is_finished(&self) -> bool {
+        return self.thread.is_finished();
+    }
+
+    /// Waits until the loop has ended, and returns the error of the board or
+    /// the feedback if an update failed.
+    pub fn wait(self) -> Result<(), BoardError> {
+        return match self.thread.join() {
+            Ok(r) => r,
+            Err(e) => std::panic::resume_unwind(e),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+    use crate::board::BoardType;
+    use crate::motor::Motor;
+
+    /// A simulated motor that reads its duty cycle from the recorded pin
+    /// traffic. It reaches `gain` counts per second at full duty with a time
+    /// constant of 100 ms, and every measurement advances it by 20 ms.
+    struct Plant {
+        backend: MemoryBackend,
+        pin: u8,
+        gain: f32,
+        speed: f32,
+    }
+
+    impl SpeedFeedback for Plant {
+        fn counts_per_second(&mut self) -> Result<f32, BackendError> {
+            let duty = match self.backend.actions(self.pin).last() {
+                Some(PinAction::Pwm { period, pulse_width }) => pulse_width.as_secs_f32() / period.as_secs_f32(),
+                Some(PinAction::High) => 1.0,
+                _ => 0.0,
+            };
+            self.speed += (self.gain * duty - self.speed) * 0.2;
+            return Ok(self.speed);
+        }
+
+        fn counts_per_revolution(&self) -> u32 {
+            return 120;
+        }
+    }
+
+    fn controller(backend: &MemoryBackend) -> SpeedController<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        board.set_shift_register_pins(16, 20, 19);
+        for (slot, pin) in [(1, 21), (2, 26)] {
+            let mut motor = Motor::with_backend(backend.clone());
+            motor.set_pin(pin).unwrap();
+            board.set_motor(motor, slot).unwrap();
+            board.set_motor_frequency(slot, 1000).unwrap();
+        }
+
+        let mut controller = SpeedController::new(&SharedBoard::new(board));
+        controller.set_interval(Duration::from_millis(1));
+        return controller;
+    }
+
+    #[test]
+    fn simulated_plant_test() {
+        let backend = MemoryBackend::new();
+        let mut controller = controller(&backend);
+        controller.set_pid(1, Pid::new(0.001, 0.2, 0.0)).unwrap();
+        controller.set_pid(2, Pid::new(0.001, 0.2, 0.0)).unwrap();
+        controller.set_output_limits(2, 0.0, 1.0).unwrap();
+
+        // the same duty runs the wheels at different speeds
+        let left = Plant { backend: backend.clone(), pin: 21, gain: 1000.0, speed: 0.0 };
+        let right = Plant { backend: backend.clone(), pin: 26, gain: 800.0, speed: 0.0 };
+        let left = controller.control(1, TargetSpeed::Rpm(300.0), Feedback::Custom(Box::new(left))).unwrap();
+        let right = controller.control(2, TargetSpeed::CountsPerSecond(600.0), Feedback::Custom(Box::new(right))).unwrap();
+        assert_eq!(600.0, left.target());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && ((left.measured() - 600.0).abs() > 5.0 || (right.measured() - 600.0).abs() > 5.0) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!((left.measured() - 600.0).abs() <= 5.0, "left {:?}", left.measured());
+        assert!((right.measured() - 600.0).abs() <= 5.0, "right {:?}", right.measured());
+        assert!(right.output() > left.output());
+
+        left.stop();
+        right.stop();
+        left.wait().unwrap();
+        right.wait().unwrap();
+        assert_eq!(Some(&PinAction::Low), backend.actions(21).last());
+        assert_eq!(Some(&PinAction::Low), backend.actions(26).last());
+    }
+
+    /// A motor that is always measured at the same speed.
+    struct Fixed(f32);
+
+    impl SpeedFeedback for Fixed {
+        fn counts_per_second(&mut self) -> Result<f32, BackendError> {
+            return Ok(self.0);
+        }
+    }
+
+    #[test]
+    fn duty_only_updates_test() {
+        let backend = MemoryBackend::new();
+        let mut controller = controller(&backend);
+        controller.set_pid(1, Pid::new(0.001, 0.0, 0.0)).unwrap();
+        backend.clear();
+
+        // the output keeps its sign, so the directions are latched once
+        let handle = controller.control(1, TargetSpeed::CountsPerSecond(100.0), Feedback::Custom(Box::new(Fixed(0.0)))).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && backend.actions(21).len() < 10 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let latches = backend.actions(19).iter().filter(|a| **a == PinAction::High).count();
+        assert_eq!(1, latches);
+        assert!(backend.actions(21).len() >= 10);
+        assert_eq!(4, controller.board().lock().get_directions() & (4 | 8));
+
+        // a reversal latches the directions again
+        handle.set_target(TargetSpeed::CountsPerSecond(-100.0)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && handle.output() >= 0.0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.stop();
+        handle.wait().unwrap();
+        let latches = backend.actions(19).iter().filter(|a| **a == PinAction::High).count();
+        assert_eq!(2, latches);
+        assert_eq!(8, controller.board().lock().get_directions() & (4 | 8));
+    }
+
+    #[test]
+    fn encoder_feedback_test() {
+        let backend = MemoryBackend::new();
+        let mut controller = controller(&backend);
+        controller.set_pid(1, Pid::new(0.001, 0.0, 0.0)).unwrap();
+        assert!(matches!(controller.control(1, TargetSpeed::CountsPerSecond(100.0), Feedback::Encoder),
+            Err(BoardError::MotorError(MotorError::EncoderNotFound))));
+
+        let encoder = Encoder::with_backend(BoardType::BCM, &backend, 22, 23).unwrap();
+        controller.board().lock().attach_encoder(1, encoder).unwrap();
+        assert!(matches!(controller.control(1, TargetSpeed::Rpm(100.0), Feedback::Encoder),
+            Err(BoardError::MotorError(MotorError::InvalidSpeed))));
+
+        // the encoder does not count, so the motor is driven forward
+        let handle = controller.control(1, TargetSpeed::CountsPerSecond(100.0), Feedback::Encoder).unwrap();
+        while handle.output() == 0.0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(0.0, handle.measured());
+        assert!(handle.set_target(TargetSpeed::CountsPerSecond(f32::NAN)).is_err());
+
+        // a newer loop takes over without stopping the motor
+        let newer = controller.control(1, TargetSpeed::CountsPerSecond(0.0), Feedback::Encoder).unwrap();
+        handle.wait().unwrap();
+        newer.stop();
+        newer.wait().unwrap();
+        assert_eq!(Some(&PinAction::Low), backend.actions(21).last());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::{BoardError, BoardType};
+use crate::motor::{Direction, Motor, MotorConfig, MotorError, SlotState};
+use motor_shield_core::directions;
+use motor_shield_core::shift_register::ShiftRegister;
+use core::time::Duration;
+
+/// # A stack of motor shields
+///
+/// Shields can be stacked by daisy-chaining their 74HC595 registers: the
+/// QH' output of one shield goes to DIR_SER of the next, while all shields
+/// share DIR_CLK and DIR_LATCH. Motors are addressed as (shield, slot), both
+/// starting at 1. Shield 1 is the shield that is connected to the serial pin.
+/// Every shield gets its own direction byte, and all bytes are latched at
+/// once, so the directions of all shields change at the same moment.
+pub struct ShieldStack<B: GpioBackend = RppalBackend> {
+    /// the backend that the shift register pins are claimed from
+    backend: B,
+    /// the chain of shift registers of all shields
+    register: Option<ShiftRegister<B::Output>>,
+    /// the motors of every shield
+    shields: Vec<[Option<Motor<B>>; 4]>,
+    /// the numbering of the pins that are passed to the stack
+    board_type: BoardType,
+}
+
+impl ShieldStack {
+    /// Creates a new stack of the specified number of shields that drives
+    /// its pins using rppal.
+    pub fn new(t: BoardType, count: usize) -> ShieldStack {
+        return ShieldStack::with_backend(t, RppalBackend, count);
+    }
+}
+
+impl<B: GpioBackend> ShieldStack<B> {
+    /// Creates a new stack of the specified number of shields that claims
+    /// its shift register pins from the specified backend.
+    pub fn with_backend(t: BoardType, backend: B, count: usize) -> ShieldStack<B> {
+        println!("creating new stack of {:?} shields", count);
+        let mut shields = Vec::with_capacity(count);
+        for _ in 0..count {
+            shields.push([None, None, None, None]);
+        }
+
+        return ShieldStack {
+            backend,
+            register: None,
+            shields,
+            board_type: t,
+        };
+    }
+
+    /// Returns the number of shields in the stack.
+    pub fn len(&self) -> usize {
+        return self.shields.len();
+    }
+
+    /// Returns whether the stack has no shields.
+    pub fn is_empty(&self) -> bool {
+        return self.shields.is_empty();
+    }
+
+    /// Sets the shared shift register pins of the stack. The pin numbers are
+    /// interpreted using the board type of the stack.
+    pub fn set_shift_register_pins(&mut self, ser: u8, clk: u8, lat: u8) -> Result<(), BoardError> {
+        println!("setting shift register pins latch={:?} clock={:?} serial={:?}", lat, clk, ser);
+        let serial = self.claim_output(ser)?;
+        let clock = self.claim_output(clk)?;
+        let latch = self.claim_output(lat)?;
+        self.register = Some(ShiftRegister::new(serial, clock, latch));
+
+        return Ok(());
+    }
+
+    /// Sets the shared shift register pins of the stack using pins that were
+    /// created outside of the backend.
+    pub fn set_shift_register_outputs(&mut self, ser: B::Output, clk: B::Output, lat: B::Output) {
+        println!("setting shift register outputs");
+        self.register = Some(ShiftRegister::new(ser, clk, lat));
+    }
+
+    /// Sets a motor in a slot of a shield. The motor takes the numbering of
+    /// the stack.
+    pub fn set_motor(&mut self, m: Motor<B>, shield: usize, slot: usize) -> Result<(), BoardError> {
+        self.check_slot(shield, slot)?;
+        println!("set motor {:?} of shield {:?}", slot, shield);
+        let mut m = m;
+        m.set_board_type(self.board_type);
+        self.shields[shield - 1][slot - 1] = Some(m);
+
+        return Ok(());
+    }
+
+    /// Returns a mutable reference to the motor in a slot of a shield.
+    pub fn get_motor(&mut self, shield: usize, slot: usize) -> Result<&mut Motor<B>, BoardError> {
+        self.check_slot(shield, slot)?;
+        return match &mut self.shields[shield - 1][slot - 1] {
+            Some(m) => Ok(m),
+            None => Err(BoardError::MotorError(MotorError::MotorNotFound)),
+        };
+    }
+
+    /// Calculates the direction byte of every shield, in the order of the
+    /// shields in the stack.
+    pub fn calculate_directions(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.shields.len());
+        for motors in self.shields.iter() {
+            let mut slots: [Option<SlotState>; 4] = [None; 4];
+            for (i, motor) in motors.iter().enumerate() {
+                slots[i] = motor.as_ref().map(|m| m.get_state());
+            }
+            bytes.push(directions::calculate_states(slots));
+        }
+
+        return bytes;
+    }
+
+    /// Pushes the direction bytes of all shields onto the chain of shift
+    /// registers and latches them in a single latch cycle.
+    pub fn update_shift_register(&mut self) -> Result<(), BoardError> {
+        let bytes = self.calculate_directions();
+        let register = match self.register.as_mut() {
+            Some(r) => r,
+            None => return Err(BoardError::RegisterPinNotSet),
+        };
+
+        println!("\tpushing {:?} bytes to shift register", bytes.len());
+        register.push(&bytes).map_err(BoardError::pin)?;
+
+        return Ok(());
+    }
+
+    /// Changes the direction of a motor and pushes the directions of all
+    /// shields.
+    pub fn change_motor_direction(&mut self, shield: usize, slot: usize, d: Direction) -> Result<(), BoardError> {
+        println!("change direction of motor {:?} of shield {:?} to {:?}", slot, shield, d);
+        self.get_motor(shield, slot)?.set_direction(d);
+        return self.update_shift_register();
+    }
+
+    /// Inverts the direction of a motor and pushes the directions of all
+    /// shields.
+    pub fn invert_motor_direction(&mut self, shield: usize, slot: usize) -> Result<(), BoardError> {
+        println!("inverting direction of motor {:?} of shield {:?}", slot, shield);
+        self.get_motor(shield, slot)?.invert_direction();
+        return self.update_shift_register();
+    }
+
+    /// Starts a motor using a motor configuration. The directions of all
+    /// shields are pushed first.
+    pub fn start_motor_config(&mut self, shield: usize, slot: usize, mc: MotorConfig) -> Result<(), BoardError> {
+        println!("start motor {:?} of shield {:?} with config {:?}", slot, shield, mc);
+        self.get_motor(shield, slot)?.set_stop_mode(None);
+        self.update_shift_register()?;
+        let motor = self.get_motor(shield, slot)?;
+        motor.start(mc).map_err(BoardError::MotorError)?;
+
+        return Ok(());
+    }
+
+    /// Starts a motor using a pulse cycle and pulse width.
+    pub fn start_motor_pwm(&mut self, shield: usize, slot: usize, cycle: Duration, width: Duration) -> Result<(), BoardError> {
+        return self.start_motor_config(shield, slot, MotorConfig::new_pwm(cycle, width));
+    }
+
+    /// Starts a motor on full speed.
+    pub fn start_motor_full(&mut self, shield: usize, slot: usize) -> Result<(), BoardError> {
+        return self.start_motor_config(shield, slot, MotorConfig::new_full());
+    }
+
+    /// Stops a motor.
+    pub fn stop_motor(&mut self, shield: usize, slot: usize) -> Result<(), BoardError> {
+        println!("stopping motor {:?} of shield {:?}", slot, shield);
+        let motor = self.get_motor(shield, slot)?;
+        motor.stop().map_err(BoardError::MotorError)?;
+
+        return Ok(());
+    }
+
+    /// Claims a shift register pin from the backend.
+    fn claim_output(&self, pin: u8) -> Result<B::Output, BoardError> {
+        let bcm = self.board_type.to_bcm(pin).map_err(|e| BoardError::Backend(e.into()))?;
+        return self.backend.output(bcm).map_err(BoardError::Backend);
+    }
+
+    /// Checks that the shield and the slot exist in the stack.
+    fn check_slot(&self, shield: usize, slot: usize) -> Result<(), BoardError> {
+        if !(1..=self.shields.len()).contains(&shield) {
+            return Err(BoardError::ShieldIndexOutOfBounds);
+        }
+
+        if !(1..=4).contains(&slot) {
+            return Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds));
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, PinAction};
+
+    #[test]
+    fn combined_bytes_test() {
+        let backend = MemoryBackend::new();
+        let mut stack = ShieldStack::with_backend(BoardType::BCM, backend.clone(), 3);
+        stack.set_shift_register_pins(16, 20, 19).unwrap();
+
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(21).unwrap();
+        stack.set_motor(motor, 2, 1).unwrap();
+        let mut motor = Motor::with_backend(backend.clone());
+        motor.set_pin(26).unwrap();
+        stack.set_motor(motor, 1, 3).unwrap();
+        stack.change_motor_direction(2, 1, Direction::Counterclockwise).unwrap();
+        assert_eq!(vec![1, 8, 0], stack.calculate_directions());
+
+        // all three bytes are latched at once
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+        assert_eq!(48, backend.actions(20).len());
+
+        // the byte of the last shield is shifted first
+        let mut serial = vec![PinAction::Low; 24];
+        serial[8 + 4] = PinAction::High;
+        serial[16 + 7] = PinAction::High;
+        assert_eq!(serial, backend.actions(16));
+
+        backend.clear();
+        stack.start_motor_full(2, 1).unwrap();
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+        assert_eq!(vec![PinAction::Low, PinAction::High], backend.actions(19));
+    }
+
+    #[test]
+    fn invalid_slot_test() {
+        let mut stack = ShieldStack::with_backend(BoardType::BCM, MemoryBackend::new(), 2);
+        assert!(matches!(stack.start_motor_full(3, 1), Err(BoardError::ShieldIndexOutOfBounds)));
+        assert!(matches!(stack.start_motor_full(1, 5),
+            Err(BoardError::MotorError(MotorError::MotorIndexOutOfBounds))));
+        assert!(matches!(stack.start_motor_full(1, 1),
+            Err(BoardError::MotorError(MotorError::MotorNotFound))));
+
+        let motor = Motor::with_backend(MemoryBackend::new());
+        stack.set_motor(motor, 1, 1).unwrap();
+        assert!(matches!(stack.start_motor_full(1, 1), Err(BoardError::RegisterPinNotSet)));
+    }
+}
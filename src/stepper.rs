@@ -0,0 +1,708 @@
+use crate::backend::{GpioBackend, RppalBackend};
+use crate::board::BoardError;
+use crate::limit::LimitSwitch;
+use crate::motor::{Direction, MotorConfig, MotorError};
+use crate::shared::SharedBoard;
+use motor_shield_core::stepper;
+use core::time::Duration;
+use std::thread;
+use std::time::Instant;
+
+pub use motor_shield_core::stepper::StepMode;
+
+/// The two L293D channels that drive the coils of a stepper motor. Coil A is
+/// on the first slot and coil B on the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepperPort {
+    M1M2,
+    M3M4,
+}
+
+impl StepperPort {
+    /// Returns the motor slots of coil A and coil B.
+    pub fn slots(self) -> (usize, usize) {
+        return match self {
+            StepperPort::M1M2 => (1, 2),
+            StepperPort::M3M4 => (3, 4),
+        };
+    }
+}
+
+/// The moves of a homing run, see `Stepper::home`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Homing {
+    /// The direction in which the limit switch is, clockwise is forward.
+    pub direction: Direction,
+    /// The speed in revolutions per minute at which the switch is sought.
+    pub seek_rpm: f32,
+    /// The speed in revolutions per minute of the final approach.
+    pub approach_rpm: f32,
+    /// The number of full steps to move away from the switch once it opens.
+    pub back_off: u32,
+    /// The number of full steps of every move after which the homing run
+    /// fails.
+    pub max_steps: u32,
+}
+
+impl Default for Homing {
+    /// Returns a homing run with the switch behind the motor, seeking at 60
+    /// rpm and approaching at 6 rpm.
+    fn default() -> Homing {
+        return Homing {
+            direction: Direction::Counterclockwise,
+            seek_rpm: 60.0,
+            approach_rpm: 6.0,
+            back_off: 20,
+            max_steps: 10_000,
+        };
+    }
+}
+
+/// # A bipolar stepper motor
+///
+/// A stepper motor uses two motor slots of the board, one for every coil.
+/// The enables of both slots are held high while the motor is energized, and
+/// the polarity of the coils is set through the direction byte of the shift
+/// register, so both coils change in a single latch. The board is shared, so
+/// a second stepper or DC motors can use the other slots. Motors have to be
+/// set in both slots for their enable pins.
+pub struct Stepper<B: GpioBackend = RppalBackend> {
+    /// the board that the coils are on
+    board: SharedBoard<B>,
+    /// the slots of coil A and coil B
+    slots: (usize, usize),
+    /// the number of full steps in one revolution
+    steps_per_revolution: u32,
+    /// the way in which the coils are driven
+    mode: StepMode,
+    /// the position in the coil sequence, in phase units
+    phase: i64,
+    /// the position of the motor in phase units, so it does not depend on
+    /// the mode
+    position: i64,
+    /// the speed in revolutions per minute
+    rpm: f32,
+    /// the moment of the last step
+    last_step: Option<Instant>,
+    /// whether the coils are energized
+    energized: bool,
+    /// whether the enables of the coils are held fully high, instead of
+    /// being modulated for microstepping
+    full_enables: bool,
+    /// whether position zero was found with a limit switch
+    homed: bool,
+    /// the lowest and the highest position that the motor may move to, in
+    /// phase units
+    soft_limits: Option<(i64, i64)>,
+}
+
+impl<B: GpioBackend> Stepper<B> {
+    /// The speed that is used until it is set.
+    pub const DEFAULT_RPM: f32 = 60.0;
+
+    /// The time between two reads of the limit switch while homing.
+    const HOMING_POLL: Duration = Duration::from_millis(1);
+
+    /// Creates a new stepper motor on the specified port of the shared board,
+    /// with the number of full steps in one revolution, which is 200 for
+    /// most motors. The motor starts in full step mode, released.
+    pub fn new(board: &SharedBoard<B>, port: StepperPort, steps_per_revolution: u32) -> Result<Stepper<B>, BoardError> {
+        if steps_per_revolution == 0 {
+            return Err(BoardError::MotorError(MotorError::InvalidStepsPerRevolution));
+        }
+
+        let slots = port.slots();
+        {
+            let mut b = board.lock();
+            b.get_motor(slots.0).map_err(BoardError::MotorError)?;
+            b.get_motor(slots.1).map_err(BoardError::MotorError)?;
+        }
+
+        println!("creating stepper on {:?} with {:?} steps", port, steps_per_revolution);
+        return Ok(Stepper {
+            board: board.clone(),
+            slots,
+            steps_per_revolution,
+            mode: StepMode::Full,
+            phase: 0,
+            position: 0,
+            rpm: Self::DEFAULT_RPM,
+            last_step: None,
+            energized: false,
+            full_enables: false,
+            homed: false,
+            soft_limits: None,
+        });
+    }
+
+    /// Returns the shared board.
+    pub fn board(&self) -> SharedBoard<B> {
+        return self.board.clone();
+    }
+
+    /// Sets the way in which the coils are driven. The position in the coil
+    /// sequence moves back to the nearest step of the mode on the next step.
+    /// Microstepping divides a full step in 2, 4, 8 or 16 microsteps. The
+    /// position and the soft limits are kept, in steps of the new mode.
+    pub fn set_mode(&mut self, mode: StepMode) -> Result<(), BoardError> {
+        if !mode.is_valid() {
+            return Err(BoardError::MotorError(MotorError::InvalidStepMode));
+        }
+
+        println!("set step mode to {:?}", mode);
+        self.mode = mode;
+        return Ok(());
+    }
+
+    /// Returns the way in which the coils are driven.
+    pub fn get_mode(&self) -> StepMode {
+        return self.mode;
+    }
+
+    /// Returns the number of steps of the current mode in one revolution.
+    pub fn steps_per_revolution(&self) -> u32 {
+        return self.mode.steps_per_revolution(self.steps_per_revolution);
+    }
+
+    /// Sets the speed in revolutions per minute at which `step` moves.
+    pub fn set_rpm(&mut self, rpm: f32) -> Result<(), BoardError> {
+        if stepper::step_interval(rpm, self.steps_per_revolution()).is_none() {
+            return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+        }
+
+        println!("set stepper speed to {:?} rpm", rpm);
+        self.rpm = rpm;
+        return Ok(());
+    }
+
+    /// Returns the speed in revolutions per minute.
+    pub fn get_rpm(&self) -> f32 {
+        return self.rpm;
+    }
+
+    /// Returns the time between two steps at the current speed and mode.
+    pub fn step_interval(&self) -> Duration {
+        return match stepper::step_interval(self.rpm, self.steps_per_revolution()) {
+            Some(i) => i,
+            None => Duration::ZERO,
+        };
+    }
+
+    /// Returns the position in steps of the current mode, rounded down.
+    /// Forward counts up.
+    pub fn get_position(&self) -> i64 {
+        return self.position.div_euclid(self.mode.stride());
+    }
+
+    /// Sets the position in steps of the current mode without moving the
+    /// motor.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position * self.mode.stride();
+    }
+
+    /// Returns whether the coils are energized, which holds the motor in
+    /// place.
+    pub fn is_energized(&self) -> bool {
+        return self.energized;
+    }
+
+    /// Returns whether position zero was found with a limit switch.
+    pub fn is_homed(&self) -> bool {
+        return self.homed;
+    }
+
+    /// Sets the lowest and the highest position in steps of the current mode
+    /// that the motor may move to, which is checked before every move.
+    /// Returns `PositionOutOfBounds` if the lowest position is above the
+    /// highest.
+    pub fn set_soft_limits(&mut self, min: i64, max: i64) -> Result<(), BoardError> {
+        if min > max {
+            return Err(BoardError::MotorError(MotorError::PositionOutOfBounds));
+        }
+
+        println!("set soft limits of stepper to {:?}..={:?}", min, max);
+        let stride = self.mode.stride();
+        self.soft_limits = Some((min * stride, max * stride));
+        return Ok(());
+    }
+
+    /// Returns the lowest and the highest position in steps of the current
+    /// mode that the motor may move to, if they are set. Limits between two
+    /// steps of the mode are rounded inward.
+    pub fn get_soft_limits(&self) -> Option<(i64, i64)> {
+        let stride = self.mode.stride();
+        return self.soft_limits.map(|(min, max)| (-(-min).div_euclid(stride), max.div_euclid(stride)));
+    }
+
+    /// Lets the motor move to any position.
+    pub fn clear_soft_limits(&mut self) {
+        self.soft_limits = None;
+    }
+
+    /// Moves the specified number of steps at the current speed, backward
+    /// if the number is negative. This blocks until the last step is made,
+    /// after which the coils stay energized to hold the motor in place.
+    /// Returns `PositionOutOfBounds` without moving if the move would end
+    /// outside of the soft limits.
+    pub fn step(&mut self, n: i64) -> Result<(), BoardError> {
+        if !self.within_soft_limits(self.position + n * self.mode.stride()) {
+            return Err(BoardError::MotorError(MotorError::PositionOutOfBounds));
+        }
+
+        println!("stepping {:?} steps in {:?} mode", n, self.mode);
+        self.align_phase();
+        for _ in 0..n.abs() {
+            self.wait_for_step();
+            self.single_step(n.signum())?;
+        }
+
+        return Ok(());
+    }
+
+    /// Moves to an absolute position at the current speed, see `step`.
+    pub fn move_to(&mut self, position: i64) -> Result<(), BoardError> {
+        return self.step(position - self.get_position());
+    }
+
+    /// Finds position zero with a limit switch. The motor moves towards the
+    /// switch at the seek speed until it closes, backs off until it opens
+    /// and then by the back off steps, and approaches the switch again at
+    /// the approach speed. The position where the switch closes again
+    /// becomes zero in every mode. The homing run is made in the current
+    /// mode. Returns `LimitNotFound` if a move takes more than the maximum
+    /// number of full steps. The soft limits are not checked while homing.
+    pub fn home(&mut self, switch: &mut LimitSwitch, homing: Homing) -> Result<(), BoardError> {
+        for rpm in [homing.seek_rpm, homing.approach_rpm] {
+            if stepper::step_interval(rpm, self.steps_per_revolution()).is_none() {
+                return Err(BoardError::MotorError(MotorError::InvalidSpeed));
+            }
+        }
+
+        println!("homing stepper towards {:?}", homing.direction);
+        let rpm = self.rpm;
+        self.homed = false;
+        let result = self.run_homing(switch, &homing);
+        self.rpm = rpm;
+        result?;
+
+        println!("stepper is homed");
+        self.position = 0;
+        self.homed = true;
+        return Ok(());
+    }
+
+    /// Turns off both coils, so the motor can turn freely. The position is
+    /// kept.
+    pub fn release(&mut self) -> Result<(), BoardError> {
+        println!("releasing stepper");
+        let release = stepper::coil_state(0);
+        let mut board = self.board.lock();
+        board.set_slot_states(&[(self.slots.0, release), (self.slots.1, release)])?;
+        for slot in [self.slots.0, self.slots.1] {
+            board.get_motor(slot).map_err(BoardError::MotorError)?.stop().map_err(BoardError::MotorError)?;
+        }
+        self.energized = false;
+        self.full_enables = false;
+
+        return Ok(());
+    }
+
+    /// Makes the moves of a homing run at the speeds of the run.
+    fn run_homing(&mut self, switch: &mut LimitSwitch, homing: &Homing) -> Result<(), BoardError> {
+        let toward = match homing.direction {
+            Direction::Clockwise => 1,
+            Direction::Counterclockwise => -1,
+        };
+        let steps_per_full_step = self.mode.steps_per_revolution(1);
+        let max_steps = homing.max_steps.saturating_mul(steps_per_full_step);
+        let back_off = homing.back_off.saturating_mul(steps_per_full_step);
+        self.align_phase();
+
+        self.rpm = homing.seek_rpm;
+        self.step_until(switch, toward, Some(true), max_steps)?;
+        self.step_until(switch, -toward, Some(false), max_steps)?;
+        self.step_until(switch, -toward, None, back_off)?;
+
+        self.rpm = homing.approach_rpm;
+        return self.step_until(switch, toward, Some(true), max_steps);
+    }
+
+    /// Steps in the direction until the limit switch is in the specified
+    /// state, reading it while waiting for every step. Returns
+    /// `LimitNotFound` if it is not after the maximum number of steps.
+    /// Without a state, the motor makes exactly the maximum number of steps.
+    fn step_until(&mut self, switch: &mut LimitSwitch, direction: i64, pressed: Option<bool>, max_steps: u32) -> Result<(), BoardError> {
+        let mut steps = 0;
+        loop {
+            if let Some(p) = pressed {
+                if switch.is_pressed().map_err(BoardError::Backend)? == p {
+                    return Ok(());
+                }
+            }
+
+            if let Some(last) = self.last_step {
+                let now = Instant::now();
+                let next = last + self.step_interval();
+                if next > now {
+                    thread::sleep((next - now).min(Self::HOMING_POLL));
+                    continue;
+                }
+            }
+
+            if steps == max_steps {
+                return match pressed {
+                    Some(_) => Err(BoardError::MotorError(MotorError::LimitNotFound)),
+                    None => Ok(()),
+                };
+            }
+
+            self.single_step(direction)?;
+            steps += 1;
+        }
+    }
+
+    /// Returns the soft limits in phase units, for a planned stepper that
+    /// takes them over.
+    pub(crate) fn phase_soft_limits(&self) -> Option<(i64, i64)> {
+        return self.soft_limits;
+    }
+
+    /// Sets the soft limits in phase units, when a planned stepper returns
+    /// the stepper.
+    pub(crate) fn set_phase_soft_limits(&mut self, soft_limits: Option<(i64, i64)>) {
+        self.soft_limits = soft_limits;
+    }
+
+    /// Returns whether the position in phase units is within the soft
+    /// limits, if they are set.
+    fn within_soft_limits(&self, position: i64) -> bool {
+        return match self.soft_limits {
+            Some((min, max)) => (min..=max).contains(&position),
+            None => true,
+        };
+    }
+
+    /// Moves the position in the coil sequence back to the nearest step of
+    /// the mode.
+    pub(crate) fn align_phase(&mut self) {
+        self.phase = self.mode.align(self.phase);
+    }
+
+    /// Makes a single step forward or backward without waiting.
+    pub(crate) fn single_step(&mut self, direction: i64) -> Result<(), BoardError> {
+        self.phase += direction * self.mode.stride();
+        self.position += direction * self.mode.stride();
+        self.last_step = Some(Instant::now());
+        return self.energize();
+    }
+
+    /// Waits until the time between two steps has passed since the last
+    /// step.
+    fn wait_for_step(&self) {
+        if let Some(last) = self.last_step {
+            let next = last + self.step_interval();
+            let now = Instant::now();
+            if next > now {
+                thread::sleep(next - now);
+            }
+        }
+    }
+
+    /// Drives the coils for the current position in the coil sequence. The
+    /// direction byte sets the polarity of the coils. When microstepping, the
+    /// duty cycle of every enable follows the current through its coil,
+    /// otherwise the enables are held fully high.
+    fn energize(&mut self) -> Result<(), BoardError> {
+        let (a, b) = stepper::coil_currents(self.phase);
+        let mut board = self.board.lock();
+        board.set_slot_states(&[(self.slots.0, stepper::coil_state(a)), (self.slots.1, stepper::coil_state(b))])?;
+
+        if self.mode.is_microstep() {
+            let (da, db) = stepper::coil_duties(self.phase);
+            for (slot, duty) in [(self.slots.0, da), (self.slots.1, db)] {
+                let motor = board.get_motor(slot).map_err(BoardError::MotorError)?;
+                let (_, mc) = match MotorConfig::from_speed(duty.abs().min(1.0), motor.get_pwm_period()) {
+                    Some(c) => c,
+                    None => return Err(BoardError::MotorError(MotorError::InvalidSpeed)),
+                };
+                motor.start(mc).map_err(BoardError::MotorError)?;
+            }
+            self.full_enables = false;
+        } else if !self.full_enables {
+            for slot in [self.slots.0, self.slots.1] {
+                let motor = board.get_motor(slot).map_err(BoardError::MotorError)?;
+                motor.start(MotorConfig::new_full()).map_err(BoardError::MotorError)?;
+            }
+            self.full_enables = true;
+        }
+        self.energized = true;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BackendError, EdgeCallback, EdgeInput, MemoryBackend, PinAction};
+    use crate::board::{AMSBoard, BoardType};
+    use crate::motor::Motor;
+
+    fn shared_board(backend: &MemoryBackend) -> SharedBoard<MemoryBackend> {
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        board.set_shift_register_pins(16, 20, 19);
+        for (slot, pin) in [(1, 21), (2, 26), (3, 13), (4, 12)] {
+            let mut motor = Motor::with_backend(backend.clone());
+            motor.set_pin(pin).unwrap();
+            board.set_motor(motor, slot).unwrap();
+        }
+        return SharedBoard::new(board);
+    }
+
+    /// Returns the direction bytes that were latched, from the serial pin.
+    fn latched_bytes(backend: &MemoryBackend) -> Vec<u8> {
+        let bits: Vec<bool> = backend.actions(16).iter().map(|a| *a == PinAction::High).collect();
+        return bits.chunks(8).map(|c| c.iter().fold(0, |byte, bit| (byte << 1) | *bit as u8)).collect();
+    }
+
+    #[test]
+    fn step_modes_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let mut stepper = Stepper::new(&board, StepperPort::M1M2, 200).unwrap();
+        stepper.set_rpm(600.0).unwrap();
+        backend.clear();
+
+        // A+ B+, A- B+, A- B-, A+ B-, next to the directions of M3 and M4
+        let idle = 1 | 32;
+        stepper.step(4).unwrap();
+        assert_eq!(vec![4 | 2 | idle, 8 | 2 | idle, 8 | 16 | idle, 4 | 16 | idle], latched_bytes(&backend));
+        assert_eq!(vec![PinAction::High], backend.actions(21));
+        assert_eq!(vec![PinAction::High], backend.actions(26));
+        assert_eq!(4, stepper.get_position());
+
+        // A+, B+, then back to A+
+        backend.clear();
+        stepper.set_mode(StepMode::Wave).unwrap();
+        stepper.step(2).unwrap();
+        stepper.step(-1).unwrap();
+        assert_eq!(vec![4 | idle, 2 | idle, 4 | idle], latched_bytes(&backend));
+        assert!(backend.actions(21).is_empty());
+
+        assert_eq!(5, stepper.get_position());
+
+        // A+ B-, B-
+        backend.clear();
+        stepper.set_mode(StepMode::Half).unwrap();
+        assert_eq!(10, stepper.get_position());
+        stepper.step(-2).unwrap();
+        assert_eq!(vec![4 | 16 | idle, 16 | idle], latched_bytes(&backend));
+        assert_eq!(8, stepper.get_position());
+
+        // the position stays on the same full step in every mode
+        stepper.set_mode(StepMode::Full).unwrap();
+        assert_eq!(4, stepper.get_position());
+        stepper.set_mode(StepMode::Microstep(16)).unwrap();
+        assert_eq!(64, stepper.get_position());
+        stepper.set_position(-32);
+        stepper.set_soft_limits(-40, 40).unwrap();
+        stepper.set_mode(StepMode::Half).unwrap();
+        assert_eq!(-4, stepper.get_position());
+        assert_eq!(Some((-5, 5)), stepper.get_soft_limits());
+        assert!(stepper.step(-2).is_err());
+    }
+
+    #[test]
+    fn release_and_ports_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let mut stepper = Stepper::new(&board, StepperPort::M3M4, 200).unwrap();
+        stepper.set_rpm(600.0).unwrap();
+        backend.clear();
+
+        // coil A is on M3 and coil B on M4, next to the directions of M1 and M2
+        let idle = 4 | 2;
+        stepper.step(1).unwrap();
+        assert_eq!(vec![1 | 32 | idle], latched_bytes(&backend));
+        assert!(stepper.is_energized());
+
+        backend.clear();
+        stepper.release().unwrap();
+        assert_eq!(vec![idle], latched_bytes(&backend));
+        assert_eq!(vec![PinAction::Low], backend.actions(13));
+        assert_eq!(vec![PinAction::Low], backend.actions(12));
+        assert!(!stepper.is_energized());
+        assert!(stepper.set_rpm(0.0).is_err());
+
+        let empty = SharedBoard::new(AMSBoard::with_backend(BoardType::BCM, MemoryBackend::new()));
+        assert!(matches!(Stepper::new(&empty, StepperPort::M1M2, 200),
+            Err(BoardError::MotorError(MotorError::MotorNotFound))));
+        assert!(matches!(Stepper::new(&board, StepperPort::M1M2, 0),
+            Err(BoardError::MotorError(MotorError::InvalidStepsPerRevolution))));
+    }
+
+    #[test]
+    fn rpm_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let mut stepper = Stepper::new(&board, StepperPort::M1M2, 200).unwrap();
+
+        // 60 rpm at 200 steps is a step every 5 ms
+        stepper.set_rpm(60.0).unwrap();
+        assert_eq!(Duration::from_millis(5), stepper.step_interval());
+        stepper.set_mode(StepMode::Half).unwrap();
+        assert_eq!(Duration::from_micros(2500), stepper.step_interval());
+        assert!(stepper.set_mode(StepMode::Microstep(5)).is_err());
+
+        stepper.set_mode(StepMode::Full).unwrap();
+        let start = Instant::now();
+        stepper.step(5).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    /// Returns the pulse widths of an enable pin in microseconds.
+    fn pulse_widths(backend: &MemoryBackend, pin: u8) -> Vec<i64> {
+        return backend.actions(pin).iter().map(|a| match a {
+            PinAction::Pwm { pulse_width, .. } => pulse_width.as_micros() as i64,
+            PinAction::High => 1000,
+            PinAction::Low => 0,
+        }).collect();
+    }
+
+    #[test]
+    fn microstep_duty_sequence_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        for slot in [1, 2] {
+            board.lock().set_motor_frequency(slot, 1000).unwrap();
+        }
+        let mut stepper = Stepper::new(&board, StepperPort::M1M2, 200).unwrap();
+        stepper.set_mode(StepMode::Microstep(8)).unwrap();
+        stepper.set_rpm(600.0).unwrap();
+        backend.clear();
+
+        // one full step in 8 microsteps, coil A follows the cosine and coil
+        // B the sine of the electrical angle
+        stepper.step(8).unwrap();
+        let angles: Vec<f64> = (1..=8).map(|i| i as f64 * std::f64::consts::PI / 16.0).collect();
+        let a: Vec<i64> = angles.iter().map(|x| (1000.0 * x.cos()).round() as i64).collect();
+        let b: Vec<i64> = angles.iter().map(|x| (1000.0 * x.sin()).round() as i64).collect();
+        for (expected, actual) in [(a, pulse_widths(&backend, 21)), (b, pulse_widths(&backend, 26))] {
+            assert_eq!(expected.len(), actual.len());
+            for (e, w) in expected.iter().zip(actual.iter()) {
+                assert!((e - w).abs() <= 1, "expected {:?} got {:?}", expected, actual);
+            }
+        }
+
+        // both coils are forward until coil A turns off at the last microstep
+        let idle = 1 | 32;
+        let mut bytes = vec![4 | 2 | idle; 7];
+        bytes.push(2 | idle);
+        assert_eq!(bytes, latched_bytes(&backend));
+
+        // the next microstep reverses coil A at a low duty
+        backend.clear();
+        stepper.step(1).unwrap();
+        assert_eq!(vec![8 | 2 | idle], latched_bytes(&backend));
+        assert_eq!(vec![195], pulse_widths(&backend, 21));
+
+        // back to full steps, the enables are held high again
+        backend.clear();
+        stepper.set_mode(StepMode::Full).unwrap();
+        stepper.step(1).unwrap();
+        assert_eq!(vec![1000], pulse_widths(&backend, 21));
+        assert_eq!(vec![1000], pulse_widths(&backend, 26));
+    }
+
+    /// Returns the position of a motor on M1 and M2 from the full steps
+    /// that were latched, starting at A+ B-.
+    fn axis_position(backend: &MemoryBackend) -> i64 {
+        let order = [(4, 2), (8, 2), (8, 16), (4, 16)];
+        let mut index = 3;
+        let mut position = 0;
+        for byte in latched_bytes(backend) {
+            if let Some(next) = order.iter().position(|(a, b)| byte & (4 | 8) == *a && byte & (2 | 16) == *b) {
+                position += match (next as i64 - index as i64).rem_euclid(4) {
+                    1 => 1,
+                    3 => -1,
+                    _ => 0,
+                };
+                index = next;
+            }
+        }
+
+        return position;
+    }
+
+    /// A limit switch at the end of a simulated axis, which pulls its pin
+    /// low while the motor is at or behind the specified position.
+    struct AxisSwitch {
+        backend: MemoryBackend,
+        at: i64,
+    }
+
+    impl EdgeInput for AxisSwitch {
+        fn is_high(&mut self) -> Result<bool, BackendError> {
+            return Ok(axis_position(&self.backend) > self.at);
+        }
+
+        fn on_edge(&mut self, _callback: EdgeCallback) -> Result<(), BackendError> {
+            return Err(BackendError::Unsupported);
+        }
+    }
+
+    #[test]
+    fn homing_and_soft_limits_test() {
+        let backend = MemoryBackend::new();
+        let board = shared_board(&backend);
+        let mut stepper = Stepper::new(&board, StepperPort::M1M2, 200).unwrap();
+        stepper.set_position(500);
+        backend.clear();
+
+        let axis = AxisSwitch { backend: backend.clone(), at: -30 };
+        let mut switch = LimitSwitch::from_input(axis).unwrap();
+        switch.set_debounce(Duration::from_millis(1));
+        let homing = Homing { seek_rpm: 600.0, approach_rpm: 60.0, back_off: 10, ..Homing::default() };
+        stepper.home(&mut switch, homing).unwrap();
+
+        // zero is where the switch closes on the slow approach
+        assert!(stepper.is_homed());
+        assert_eq!(0, stepper.get_position());
+        assert_eq!(-30, axis_position(&backend));
+        assert_eq!(Stepper::<MemoryBackend>::DEFAULT_RPM, stepper.get_rpm());
+
+        // moves outside of the soft limits are refused before moving
+        stepper.set_rpm(600.0).unwrap();
+        stepper.set_soft_limits(0, 100).unwrap();
+        assert!(matches!(stepper.step(-1), Err(BoardError::MotorError(MotorError::PositionOutOfBounds))));
+        stepper.move_to(50).unwrap();
+        assert_eq!(50, stepper.get_position());
+        assert_eq!(20, axis_position(&backend));
+        assert!(matches!(stepper.move_to(101), Err(BoardError::MotorError(MotorError::PositionOutOfBounds))));
+        assert_eq!(Some((0, 100)), stepper.get_soft_limits());
+        assert!(stepper.set_soft_limits(1, 0).is_err());
+        stepper.clear_soft_limits();
+        stepper.move_to(-10).unwrap();
+
+        // a switch that never closes fails the homing run
+        let axis = AxisSwitch { backend: backend.clone(), at: -10_000 };
+        let mut switch = LimitSwitch::from_input(axis).unwrap();
+        let homing = Homing { seek_rpm: 600.0, max_steps: 20, ..Homing::default() };
+        assert!(matches!(stepper.home(&mut switch, homing), Err(BoardError::MotorError(MotorError::LimitNotFound))));
+        assert!(!stepper.is_homed());
+        assert_eq!(-30, stepper.get_position());
+
+        // homing in half step mode finds the same zero, backing off 10 full
+        // steps past where the switch opens, with moves limited to 40 full
+        // steps
+        stepper.set_mode(StepMode::Half).unwrap();
+        let axis = AxisSwitch { backend: backend.clone(), at: -30 };
+        let mut switch = LimitSwitch::from_input(axis).unwrap();
+        switch.set_debounce(Duration::from_millis(1));
+        let homing = Homing { seek_rpm: 300.0, approach_rpm: 60.0, back_off: 10, max_steps: 40, ..Homing::default() };
+        stepper.home(&mut switch, homing).unwrap();
+        assert_eq!(-30, axis_position(&backend));
+        stepper.set_mode(StepMode::Full).unwrap();
+        assert_eq!(0, stepper.get_position());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::backend::{self, GpioBackend, BackendError};
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal::pwm::{self, SetDutyCycle};
+use core::time::Duration;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+
+/// The directory of the sysfs gpio interface of the kernel.
+pub const GPIO_ROOT: &str = "/sys/class/gpio";
+
+/// The directory of the sysfs pwm interface of the kernel.
+pub const PWM_ROOT: &str = "/sys/class/pwm";
+
+/// The number of times a pin file is retried after the pin is exported. The
+/// kernel creates the files of a pin right away, but udev may take a moment
+/// to hand out the permissions.
+const EXPORT_RETRIES: u32 = 10;
+
+/// The time to wait between the retries after exporting a pin.
+const EXPORT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Exports pin or channel `n` by writing it to the export file in `parent`,
+/// unless its directory `dir` already exists. Returns whether it was
+/// exported.
+fn export(parent: &Path, dir: &Path, n: u8) -> Result<bool, BackendError> {
+    if dir.exists() {
+        return Ok(false);
+    }
+
+    fs::write(parent.join("export"), n.to_string())?;
+    return Ok(true);
+}
+
+/// Writes a value to a file of an exported pin or channel. If the pin was
+/// just exported, the write is retried until udev has handed out the
+/// permissions.
+fn write_exported(path: &Path, value: &str, exported: bool) -> Result<(), BackendError> {
+    let mut retries = 0;
+    while let Err(e) = fs::write(path, value) {
+        if !exported || retries >= EXPORT_RETRIES {
+            return Err(BackendError::Io(e));
+        }
+
+        retries += 1;
+        sleep(EXPORT_RETRY_DELAY);
+    }
+
+    return Ok(());
+}
+
+/// The backend that drives pins through the export, direction and value
+/// files of the sysfs gpio interface. This works on kernels and images where
+/// rppal can not be used. The root directory can be changed, so the backend
+/// can be pointed at a fake directory tree.
+#[derive(Debug, Clone)]
+pub struct SysfsBackend {
+    /// The directory that holds the export file and the pin directories.
+    root: PathBuf,
+}
+
+impl SysfsBackend {
+    /// Creates a new backend that uses `/sys/class/gpio`.
+    pub fn new() -> SysfsBackend {
+        return SysfsBackend::with_root(GPIO_ROOT);
+    }
+
+    /// Creates a new backend that uses the specified root directory instead
+    /// of `/sys/class/gpio`.
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> SysfsBackend {
+        return SysfsBackend { root: root.into() };
+    }
+
+    /// Returns the root directory of this backend.
+    pub fn root(&self) -> &Path {
+        return &self.root;
+    }
+
+    /// Exports the pin if needed and configures it as an output that starts
+    /// low.
+    fn claim(&self, pin: u8) -> Result<SysfsPin, BackendError> {
+        let dir = self.root.join(format!("gpio{}", pin));
+        let exported = export(&self.root, &dir, pin)?;
+        write_exported(&dir.join("direction"), "low", exported)?;
+
+        return Ok(SysfsPin {
+            root: self.root.clone(),
+            dir,
+            pin,
+            exported,
+        });
+    }
+}
+
+impl Default for SysfsBackend {
+    fn default() -> SysfsBackend {
+        return SysfsBackend::new();
+    }
+}
+
+impl GpioBackend for SysfsBackend {
+    type Output = SysfsPin;
+    type Pwm = SysfsPin;
+
+    fn output(&self, pin: u8) -> Result<SysfsPin, BackendError> {
+        return self.claim(pin);
+    }
+
+    /// Claims a pin for a pwm signal. The sysfs gpio interface can not
+    /// generate pwm, so the pin can only be switched fully on or off. The
+    /// period is ignored.
+    fn pwm(&self, pin: u8, _period: Duration) -> Result<SysfsPin, BackendError> {
+        return self.claim(pin);
+    }
+}
+
+/// An output pin of the `SysfsBackend`. The pin is unexported when it is
+/// dropped, if it was exported by the backend.
+#[derive(Debug)]
+pub struct SysfsPin {
+    /// The root directory that holds the unexport file.
+    root: PathBuf,
+    /// The directory of this pin.
+    dir: PathBuf,
+    /// The number of this pin.
+    pin: u8,
+    /// Whether the pin was exported by the backend.
+    exported: bool,
+}
+
+impl SysfsPin {
+    /// The maximum duty cycle of a sysfs pin. Only zero and the maximum can
+    /// be set.
+    const MAX_DUTY: u16 = 100;
+
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        return self.pin;
+    }
+
+    /// Writes a value to the value file of this pin.
+    fn write_value(&mut self, value: &str) -> Result<(), BackendError> {
+        fs::write(self.dir.join("value"), value)?;
+        return Ok(());
+    }
+}
+
+impl Drop for SysfsPin {
+    fn drop(&mut self) {
+        if self.exported {
+            let _ = fs::write(self.root.join("unexport"), self.pin.to_string());
+        }
+    }
+}
+
+impl digital::ErrorType for SysfsPin {
+    type Error = BackendError;
+}
+
+impl OutputPin for SysfsPin {
+    fn set_low(&mut self) -> Result<(), BackendError> {
+        return self.write_value("0");
+    }
+
+    fn set_high(&mut self) -> Result<(), BackendError> {
+        return self.write_value("1");
+    }
+}
+
+impl pwm::ErrorType for SysfsPin {
+    type Error = BackendError;
+}
+
+impl SetDutyCycle for SysfsPin {
+    fn max_duty_cycle(&self) -> u16 {
+        return SysfsPin::MAX_DUTY;
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), BackendError> {
+        if duty == 0 {
+            return self.set_low();
+        }
+
+        if duty >= SysfsPin::MAX_DUTY {
+            return self.set_high();
+        }
+
+        return Err(BackendError::Unsupported);
+    }
+}
+
+/// A pwm chip of the sysfs pwm interface. Its channels generate the pwm
+/// signal in hardware, which does not jitter like the software pwm of rppal.
+/// The root directory can be changed, so the chip can be pointed at a fake
+/// directory tree.
+#[derive(Debug, Clone)]
+pub struct PwmChip {
+    /// The directory of the chip, like `/sys/class/pwm/pwmchip0`.
+    dir: PathBuf,
+}
+
+impl PwmChip {
+    /// Creates a new chip that uses `/sys/class/pwm/pwmchipN`.
+    pub fn new(chip: u8) -> PwmChip {
+        return PwmChip::with_root(PWM_ROOT, chip);
+    }
+
+    /// Creates a new chip that uses the specified root directory instead of
+    /// `/sys/class/pwm`.
+    pub fn with_root<P: Into<PathBuf>>(root: P, chip: u8) -> PwmChip {
+        return PwmChip { dir: root.into().join(format!("pwmchip{}", chip)) };
+    }
+
+    /// Claims a channel of this chip. The channel is exported if needed,
+    /// configured with the specified period and enabled with a duty cycle
+    /// of zero.
+    pub fn channel(&self, channel: u8, period: Duration) -> Result<SysfsPwm, BackendError> {
+        let dir = self.dir.join(format!("pwm{}", channel));
+        let exported = export(&self.dir, &dir, channel)?;
+
+        // the duty cycle can never be longer than the period, so it is
+        // cleared before the period is changed
+        write_exported(&dir.join("duty_cycle"), "0", exported)?;
+        fs::write(dir.join("period"), period.as_nanos().to_string())?;
+        fs::write(dir.join("enable"), "1")?;
+
+        return Ok(SysfsPwm {
+            chip: self.dir.clone(),
+            dir,
+            channel,
+            period,
+            exported,
+        });
+    }
+}
+
+/// A channel of a `PwmChip`. The channel is disabled when it is dropped, and
+/// unexported if it was exported by the chip.
+#[derive(Debug)]
+pub struct SysfsPwm {
+    /// The directory of the chip that holds the unexport file.
+    chip: PathBuf,
+    /// The directory of this channel.
+    dir: PathBuf,
+    /// The number of this channel.
+    channel: u8,
+    /// The period of the pwm signal.
+    period: Duration,
+    /// Whether the channel was exported by the chip.
+    exported: bool,
+}
+
+impl SysfsPwm {
+    /// Returns the number of this channel.
+    pub fn channel(&self) -> u8 {
+        return self.channel;
+    }
+
+    /// Returns the period of the pwm signal of this channel.
+    pub fn period(&self) -> Duration {
+        return self.period;
+    }
+}
+
+impl Drop for SysfsPwm {
+    fn drop(&mut self) {
+        let _ = fs::write(self.dir.join("enable"), "0");
+        if self.exported {
+            let _ = fs::write(self.chip.join("unexport"), self.channel.to_string());
+        }
+    }
+}
+
+impl pwm::ErrorType for SysfsPwm {
+    type Error = BackendError;
+}
+
+impl SetDutyCycle for SysfsPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        return backend::max_duty_for(self.period);
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), BackendError> {
+        let max = self.max_duty_cycle();
+        let width = backend::pulse_width_for(self.period, duty.min(max), max);
+        fs::write(self.dir.join("duty_cycle"), width.as_nanos().to_string())?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::board::{AMSBoard, BoardType};
+    use crate::motor::Motor;
+    use crate::servo::{Servo, ServoConfig};
+
+    fn read(root: &Path, file: &str) -> String {
+        return fs::read_to_string(root.join(file)).unwrap();
+    }
+
+    #[test]
+    fn claim_exported_pin_test() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("gpio16")).unwrap();
+        let backend = SysfsBackend::with_root(root.path());
+
+        let mut pin = backend.output(16).unwrap();
+        assert_eq!("low", read(root.path(), "gpio16/direction"));
+        assert!(!root.path().join("export").exists());
+
+        pin.set_high().unwrap();
+        assert_eq!("1", read(root.path(), "gpio16/value"));
+        pin.set_low().unwrap();
+        assert_eq!("0", read(root.path(), "gpio16/value"));
+
+        drop(pin);
+        assert!(!root.path().join("unexport").exists());
+    }
+
+    #[test]
+    fn export_missing_pin_test() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = SysfsBackend::with_root(root.path());
+
+        // the fake tree has no kernel to create the pin directory
+        assert!(matches!(backend.output(5), Err(BackendError::Io(_))));
+        assert_eq!("5", read(root.path(), "export"));
+    }
+
+    #[test]
+    fn pwm_is_only_on_or_off_test() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("gpio21")).unwrap();
+        let backend = SysfsBackend::with_root(root.path());
+
+        let mut pin = backend.pwm(21, Duration::from_millis(20)).unwrap();
+        pin.set_duty_cycle_fully_on().unwrap();
+        assert_eq!("1", read(root.path(), "gpio21/value"));
+        assert!(matches!(pin.set_duty_cycle_percent(50), Err(BackendError::Unsupported)));
+    }
+
+    #[test]
+    fn board_with_sysfs_backend_test() {
+        let root = tempfile::tempdir().unwrap();
+        for pin in [16, 19, 20, 21] {
+            fs::create_dir(root.path().join(format!("gpio{}", pin))).unwrap();
+        }
+        let backend = SysfsBackend::with_root(root.path());
+
+        let mut board = AMSBoard::with_backend(BoardType::BCM, backend.clone());
+        let mut motor = Motor::with_backend(backend);
+        motor.set_pin(21).unwrap();
+        board.set_shift_register_pins(16, 20, 19);
+        board.set_motor(motor, 1).unwrap();
+        board.start_motor_full(1).unwrap();
+
+        // the last bit of 0b00000100 is low, the latch is opened
+        assert_eq!("0", read(root.path(), "gpio16/value"));
+        assert_eq!("1", read(root.path(), "gpio19/value"));
+        assert_eq!("1", read(root.path(), "gpio21/value"));
+    }
+
+    #[test]
+    fn pwm_channel_test() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("pwmchip0/pwm1")).unwrap();
+        let chip = PwmChip::with_root(root.path(), 0);
+
+        let mut channel = chip.channel(1, Duration::from_millis(20)).unwrap();
+        assert_eq!("20000000", read(root.path(), "pwmchip0/pwm1/period"));
+        assert_eq!("0", read(root.path(), "pwmchip0/pwm1/duty_cycle"));
+        assert_eq!("1", read(root.path(), "pwmchip0/pwm1/enable"));
+
+        channel.set_duty_cycle_percent(25).unwrap();
+        assert_eq!("5000000", read(root.path(), "pwmchip0/pwm1/duty_cycle"));
+
+        drop(channel);
+        assert_eq!("0", read(root.path(), "pwmchip0/pwm1/enable"));
+        assert!(!root.path().join("pwmchip0/unexport").exists());
+    }
+
+    #[test]
+    fn export_missing_channel_test() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("pwmchip2")).unwrap();
+        let chip = PwmChip::with_root(root.path(), 2);
+
+        // the fake tree has no kernel to create the channel directory
+        assert!(matches!(chip.channel(0, Duration::from_millis(20)), Err(BackendError::Io(_))));
+        assert_eq!("0", read(root.path(), "pwmchip2/export"));
+    }
+
+    #[test]
+    fn motor_and_servo_on_pwm_channel_test() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("pwmchip0/pwm0")).unwrap();
+        fs::create_dir_all(root.path().join("pwmchip0/pwm1")).unwrap();
+        let chip = PwmChip::with_root(root.path(), 0);
+
+        let mut board = AMSBoard::with_backend(BoardType::BCM, MemoryBackend::new());
+        let mut motor = Motor::with_backend(MemoryBackend::new());
+        motor.set_pwm_channel(&chip, 0).unwrap();
+        board.set_motor(motor, 1).unwrap();
+        board.start_motor_pwm(1, Duration::from_millis(20), Duration::from_millis(5)).unwrap();
+        assert_eq!("5000000", read(root.path(), "pwmchip0/pwm0/duty_cycle"));
+
+        let mut servo: Servo<MemoryBackend> = Servo::new_servo_from_channel(ServoConfig::new_default(), &chip, 1).unwrap();
+        assert_eq!("20000000", read(root.path(), "pwmchip0/pwm1/period"));
+        assert_eq!("1575000", read(root.path(), "pwmchip0/pwm1/duty_cycle"));
+        servo.set_angle(0).unwrap();
+        assert_eq!("600000", read(root.path(), "pwmchip0/pwm1/duty_cycle"));
+    }
+}
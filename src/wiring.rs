@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+/// The pins of the Arduino header that are used by the motor shield.
+///
+/// | Pin | Shield    | Function                          |
+/// |-----|-----------|-----------------------------------|
+/// | D3  | PWM2B     | enable of M2                      |
+/// | D4  | DIR_CLK   | clock of the shift register       |
+/// | D5  | PWM0B     | enable of M4                      |
+/// | D6  | PWM0A     | enable of M3                      |
+/// | D7  | DIR_EN    | output enable of the register     |
+/// | D8  | DIR_SER   | serial data of the shift register |
+/// | D9  | SERVO_1   | pulse of servo 1                  |
+/// | D10 | SERVO_2   | pulse of servo 2                  |
+/// | D11 | PWM2A     | enable of M1                      |
+/// | D12 | DIR_LATCH | latch of the shift register       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArduinoPin {
+    D3,
+    D4,
+    D5,
+    D6,
+    D7,
+    D8,
+    D9,
+    D10,
+    D11,
+    D12,
+}
+
+impl ArduinoPin {
+    /// The clock of the shift register.
+    pub const DIR_CLK: ArduinoPin = ArduinoPin::D4;
+    /// The output enable of the shift register.
+    pub const DIR_EN: ArduinoPin = ArduinoPin::D7;
+    /// The serial data line of the shift register.
+    pub const DIR_SER: ArduinoPin = ArduinoPin::D8;
+    /// The latch of the shift register.
+    pub const DIR_LATCH: ArduinoPin = ArduinoPin::D12;
+
+    /// Returns the pin that enables the motor in the specified M slot, or
+    /// `None` if the slot does not exist.
+    pub fn motor_enable(slot: usize) -> Option<ArduinoPin> {
+        return match slot {
+            1 => Some(ArduinoPin::D11),
+            2 => Some(ArduinoPin::D3),
+            3 => Some(ArduinoPin::D6),
+            4 => Some(ArduinoPin::D5),
+            _ => None,
+        };
+    }
+
+    /// Returns the pin of the specified servo slot, or `None` if the slot
+    /// does not exist.
+    pub fn servo(slot: usize) -> Option<ArduinoPin> {
+        return match slot {
+            1 => Some(ArduinoPin::D9),
+            2 => Some(ArduinoPin::D10),
+            _ => None,
+        };
+    }
+}
+
+/// The wiring of the Arduino header of the motor shield to the pins of the
+/// Raspberry Pi. The pin numbers are interpreted using the board type of
+/// the board that is built from the wiring.
+#[derive(Debug, Clone, Default)]
+pub struct ShieldWiring {
+    pins: HashMap<ArduinoPin, u8>,
+    inverted: [bool; 4],
+}
+
+impl ShieldWiring {
+    /// Returns an empty wiring.
+    pub fn new() -> ShieldWiring {
+        return ShieldWiring { pins: HashMap::new(), inverted: [false; 4] };
+    }
+
+    /// Returns a wiring from a map of Arduino pins to pins of the Raspberry
+    /// Pi.
+    pub fn from_map(pins: HashMap<ArduinoPin, u8>) -> ShieldWiring {
+        return ShieldWiring { pins, inverted: [false; 4] };
+    }
+
+    /// Connects the specified Arduino pin to a pin of the Raspberry Pi.
+    pub fn connect(mut self, arduino: ArduinoPin, pin: u8) -> ShieldWiring {
+        self.pins.insert(arduino, pin);
+        return self;
+    }
+
+    /// Marks the motor in the specified M slot as wired the other way around.
+    /// A slot that does not exist is ignored.
+    pub fn invert(mut self, slot: usize) -> ShieldWiring {
+        if (1..=4).contains(&slot) {
+            self.inverted[slot - 1] = true;
+        }
+        return self;
+    }
+
+    /// Returns whether the motor in the specified M slot is wired the other
+    /// way around.
+    pub fn is_inverted(&self, slot: usize) -> bool {
+        return (1..=4).contains(&slot) && self.inverted[slot - 1];
+    }
+
+    /// Returns the pin of the Raspberry Pi that is connected to the
+    /// specified Arduino pin.
+    pub fn get(&self, arduino: ArduinoPin) -> Option<u8> {
+        return self.pins.get(&arduino).copied();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wiring_test() {
+        let wiring = ShieldWiring::new()
+            .connect(ArduinoPin::DIR_SER, 16)
+            .connect(ArduinoPin::D4, 20);
+        assert_eq!(Some(16), wiring.get(ArduinoPin::D8));
+        assert_eq!(Some(20), wiring.get(ArduinoPin::DIR_CLK));
+        assert_eq!(None, wiring.get(ArduinoPin::DIR_LATCH));
+
+        let mut map = HashMap::new();
+        map.insert(ArduinoPin::D11, 21);
+        let wiring = ShieldWiring::from_map(map);
+        assert_eq!(Some(21), wiring.get(ArduinoPin::motor_enable(1).unwrap()));
+        assert_eq!(None, ArduinoPin::motor_enable(5));
+        assert_eq!(Some(ArduinoPin::D10), ArduinoPin::servo(2));
+    }
+}